@@ -1,4 +1,19 @@
+use crate::execs::cancel::CancelToken;
+use crate::execs::diag;
+use crate::execs::env::workers_from_env;
+use crate::execs::error::{
+    panic_message, CurrentThreadError, NestedRuntimeError, RuntimeError, TimeoutError,
+};
+use crate::execs::executor::{TaskHandle, ThreadPool};
 
+/// The start of the message of the panic raised by tokio when `block_on` is called from within a runtime
+const NESTED_RUNTIME_PANIC: &str = "Cannot start a runtime from within a runtime";
+
+/// The longest `AsyncRuntime::quiesce` waits between two checks of the tasks still alive
+const QUIESCE_MAX_BACKOFF: std::time::Duration = std::time::Duration::from_millis(20);
+
+/// How long `AsyncRuntime::poll_watched` waits for a future before warning that it may be deadlocked
+const WATCHDOG_THRESHOLD: std::time::Duration = std::time::Duration::from_secs(30);
 
 /// # AsyncRuntime
 /// 
@@ -40,6 +55,10 @@
 ///
 pub struct AsyncRuntime {
     runtime: tokio::runtime::Runtime,
+    quiesced: std::sync::atomic::AtomicBool,
+    limit: Option<std::sync::Arc<tokio::sync::Semaphore>>,
+    /// The worker reserved for `poll_priority`, started on its first call, or `None` if it failed to start
+    reserved: std::sync::OnceLock<Option<ReservedWorker>>,
 }
 
 impl AsyncRuntime {
@@ -52,33 +71,202 @@ impl AsyncRuntime {
     /// # Returns
     ///
     /// A new `AsyncRuntime` instance with the specified configuration.
+    ///
+    /// If the worker threads of a multi-threaded flavor cannot be spawned, e.g. in a sandbox that forbids
+    /// spawning threads, the failure is logged and a `CurrentThread` runtime is created instead, rather than
+    /// crashing. Use `try_new` to handle the failure instead.
     pub fn new(properties: AsyncFlavor) -> Self {
+        Self::builder(properties).build()
+    }
+
+    /// Creates a new `AsyncRuntime` of the given flavor, failing if it cannot be built.
+    ///
+    /// # Returns
+    /// - `Ok(AsyncRuntime)` the runtime, of the requested flavor
+    /// - `Err(io::Error)` if the runtime could not be built, e.g. because its threads could not be spawned
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    ///
+    /// let runtime = AsyncRuntime::try_new(AsyncFlavor::WorkerThreads(2)).unwrap();
+    /// assert_eq!(runtime.poll(async { 1 + 1 }), 2);
+    /// ```
+    pub fn try_new(properties: AsyncFlavor) -> std::io::Result<Self> {
+        Self::builder(properties).try_build()
+    }
+
+    /// Wraps a tokio runtime the caller has already built, taking ownership of it.
+    ///
+    /// This gives the helpers of this type to a runtime configured with options that `AsyncFlavor` and
+    /// `AsyncRuntimeBuilder` do not cover.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::AsyncRuntime;
+    ///
+    /// let rt = tokio::runtime::Builder::new_multi_thread()
+    ///     .worker_threads(2)
+    ///     .thread_name("custom-worker")
+    ///     .enable_all()
+    ///     .build()
+    ///     .unwrap();
+    /// let runtime = AsyncRuntime::from_runtime(rt);
+    ///
+    /// let name = runtime.poll(async {
+    ///     tokio::spawn(async { std::thread::current().name().map(String::from) })
+    ///         .await
+    ///         .unwrap()
+    /// });
+    /// assert_eq!(name.as_deref(), Some("custom-worker"));
+    /// ```
+    pub fn from_runtime(rt: tokio::runtime::Runtime) -> Self {
         Self {
-            runtime: match properties {
-                AsyncFlavor::CurrentThread => tokio::runtime::Builder::new_current_thread()
-                    .enable_all()
-                    .build()
-                    .unwrap(),
-                AsyncFlavor::WorkerThreads(size) => {
-                    tokio::runtime::Builder::new_multi_thread()
-                        .worker_threads(size)
-                        .enable_all()
-                        .build()
-                        .unwrap()
-                }
-                AsyncFlavor::AllThreads => tokio::runtime::Builder::new_multi_thread()
-                    .enable_all()
-                    .build()
-                    .unwrap(),
-            },
+            runtime: rt,
+            quiesced: std::sync::atomic::AtomicBool::new(false),
+            limit: None,
+            reserved: std::sync::OnceLock::new(),
+        }
+    }
+
+    /// Creates a new AsyncRuntime on which at most `limit` futures passed to `execute` run at once.
+    ///
+    /// Every future spawned by `execute` waits for a permit before it starts, and gives it back once it
+    /// completes, so a burst of spawns cannot overwhelm the resources the futures share. The futures
+    /// passed to `poll` and its variants are not limited.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::with_max_concurrency(AsyncFlavor::WorkerThreads(4), 2);
+    /// let running = Arc::new(AtomicUsize::new(0));
+    /// let peak = Arc::new(AtomicUsize::new(0));
+    /// let (done_tx, done_rx) = std::sync::mpsc::channel();
+    ///
+    /// for _ in 0..20 {
+    ///     let (running, peak, done_tx) = (running.clone(), peak.clone(), done_tx.clone());
+    ///     runtime.execute(async move {
+    ///         let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+    ///         peak.fetch_max(now, Ordering::SeqCst);
+    ///         tokio::time::sleep(Duration::from_millis(10)).await;
+    ///         running.fetch_sub(1, Ordering::SeqCst);
+    ///         done_tx.send(()).unwrap();
+    ///     });
+    /// }
+    ///
+    /// for _ in 0..20 {
+    ///     done_rx.recv().unwrap();
+    /// }
+    /// assert_eq!(peak.load(Ordering::SeqCst), 2);
+    /// ```
+    pub fn with_max_concurrency(properties: AsyncFlavor, limit: usize) -> Self {
+        assert_ne!(limit, 0, "Cannot limit the runtime to 0 concurrent futures");
+        Self {
+            limit: Some(std::sync::Arc::new(tokio::sync::Semaphore::new(limit))),
+            ..Self::new(properties)
         }
     }
 
+    /// Creates a single threaded runtime whose clock is paused, for testing time-dependent futures.
+    ///
+    /// Time only moves forward through `advance_time`, or automatically when the runtime has nothing to do
+    /// but wait for a timer, in which case it jumps straight to that timer. Sleeps and timeouts thus complete
+    /// instantly and deterministically, whatever their duration.
+    ///
+    /// Only available with the `test-util` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::AsyncRuntime;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let runtime = AsyncRuntime::new_paused();
+    /// let started = Instant::now();
+    /// let done = Arc::new(AtomicBool::new(false));
+    ///
+    /// let flag = done.clone();
+    /// runtime.execute(async move {
+    ///     tokio::time::sleep(Duration::from_secs(3600)).await;
+    ///     flag.store(true, Ordering::SeqCst);
+    /// });
+    /// // Lets the task start its sleep
+    /// runtime.poll(tokio::task::yield_now());
+    ///
+    /// runtime.advance_time(Duration::from_secs(3599));
+    /// runtime.poll(tokio::task::yield_now());
+    /// assert!(!done.load(Ordering::SeqCst));
+    ///
+    /// runtime.advance_time(Duration::from_secs(1));
+    /// runtime.poll(tokio::task::yield_now());
+    /// assert!(done.load(Ordering::SeqCst));
+    /// assert!(started.elapsed() < Duration::from_secs(1));
+    /// ```
+    #[cfg(feature = "test-util")]
+    pub fn new_paused() -> Self {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        Self::from_runtime(rt)
+    }
+
+    /// Moves the paused clock of a runtime created with `new_paused` forward by `d`, firing the timers due by then.
+    ///
+    /// Only available with the `test-util` feature.
+    ///
+    /// # Panics
+    /// Panics if the clock of the runtime is not paused.
+    #[cfg(feature = "test-util")]
+    pub fn advance_time(&self, d: std::time::Duration) {
+        self.runtime.block_on(tokio::time::advance(d));
+    }
+
+    /// Creates an `AsyncRuntimeBuilder` for configuring a runtime of the given flavor before creating it.
+    ///
+    /// See `AsyncRuntimeBuilder` for details.
+    pub fn builder(properties: AsyncFlavor) -> AsyncRuntimeBuilder {
+        let mut builder = match properties {
+            AsyncFlavor::CurrentThread => tokio::runtime::Builder::new_current_thread(),
+            AsyncFlavor::WorkerThreads(size) => {
+                let mut builder = tokio::runtime::Builder::new_multi_thread();
+                builder.worker_threads(size);
+                builder
+            }
+            AsyncFlavor::AllThreads => tokio::runtime::Builder::new_multi_thread(),
+            AsyncFlavor::FromEnv => {
+                let default = std::thread::available_parallelism().map_or(1, |n| n.get());
+                let mut builder = tokio::runtime::Builder::new_multi_thread();
+                builder.worker_threads(workers_from_env(default));
+                builder
+            }
+        };
+        builder.enable_all();
+        AsyncRuntimeBuilder { builder }
+    }
+
     /// Schedules the given future `F` to be executed on the runtime.
     ///
     /// The `execute` method spawns a new task in the runtime and runs it asynchronously.
     ///
     /// This function is non-blocking.
+    ///
+    /// While the runtime is quiesced with `quiesce`, the future is dropped without being spawned. On a runtime
+    /// created with `with_max_concurrency`, the future waits for a permit before it starts.
     /// # Examples
     ///
     /// ```
@@ -89,12 +277,111 @@ impl AsyncRuntime {
     ///     println!("This will execute on a single thread runtime.");
     /// });
     /// ```
-
     pub fn execute<F: Send + 'static + std::future::Future>(&self, f: F)
     where
         F::Output: Send + 'static,
     {
-        self.runtime.spawn(f);
+        if self.quiesced.load(std::sync::atomic::Ordering::Acquire) {
+            return;
+        }
+        match self.limit.clone() {
+            Some(limit) => {
+                self.runtime.spawn(async move {
+                    // The semaphore is never closed
+                    let _permit = limit.acquire_owned().await;
+                    f.await
+                });
+            }
+            None => {
+                self.runtime.spawn(f);
+            }
+        }
+    }
+
+    /// Schedules the given future to be executed on the runtime, aborting it once it exceeds a CPU budget.
+    ///
+    /// The time spent in each poll of `f` is added up, and once the total exceeds `max`, `f` is dropped at the
+    /// end of that poll and a warning is logged. This is a soft limit meant for untrusted or runaway futures:
+    /// the time of a poll is wall-clock time, which approximates CPU time but also counts time the thread was
+    /// preempted or blocked inside the poll, and a single poll that never yields can run past the budget since
+    /// it cannot be interrupted. Time spent waiting between polls is not counted.
+    ///
+    /// The warning goes through the `log` and `tracing` features that are enabled, and is dropped otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "log")]
+    /// # mod recorder {
+    /// #     pub static WARNINGS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    /// #
+    /// #     pub struct Recorder;
+    /// #
+    /// #     impl log::Log for Recorder {
+    /// #         fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+    /// #             metadata.level() <= log::Level::Warn
+    /// #         }
+    /// #
+    /// #         fn log(&self, record: &log::Record<'_>) {
+    /// #             WARNINGS.lock().unwrap().push(record.args().to_string());
+    /// #         }
+    /// #
+    /// #         fn flush(&self) {}
+    /// #     }
+    /// # }
+    /// # #[cfg(feature = "log")]
+    /// # {
+    /// #     log::set_logger(&recorder::Recorder).unwrap();
+    /// #     log::set_max_level(log::LevelFilter::Warn);
+    /// # }
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    /// let (done_tx, done_rx) = std::sync::mpsc::channel();
+    ///
+    /// runtime.execute_budgeted(Duration::from_millis(50), async move {
+    ///     // 200ms of work, in chunks of 5ms
+    ///     for _ in 0..40 {
+    ///         std::thread::sleep(Duration::from_millis(5));
+    ///         tokio::task::yield_now().await;
+    ///     }
+    ///     done_tx.send(()).unwrap();
+    /// });
+    ///
+    /// // The sender was dropped along with the aborted future, before it could finish
+    /// assert!(done_rx.recv().is_err());
+    /// # #[cfg(feature = "log")]
+    /// # {
+    /// #     let warnings = recorder::WARNINGS.lock().unwrap();
+    /// #     assert_eq!(warnings.len(), 1);
+    /// #     assert!(warnings[0].contains("over its budget"));
+    /// # }
+    /// ```
+    pub fn execute_budgeted<F: Send + 'static + std::future::Future>(
+        &self,
+        max: std::time::Duration,
+        f: F,
+    ) where
+        F::Output: Send + 'static,
+    {
+        let mut f = Box::pin(f);
+        let mut spent = std::time::Duration::ZERO;
+        self.execute(std::future::poll_fn(move |cx| {
+            let started = std::time::Instant::now();
+            let poll = f.as_mut().poll(cx);
+            spent += started.elapsed();
+            match poll {
+                std::task::Poll::Ready(_) => std::task::Poll::Ready(()),
+                std::task::Poll::Pending if spent > max => {
+                    diag::warning!(
+                        "aborted task after polling it for {spent:?}, over its budget of {max:?}"
+                    );
+                    std::task::Poll::Ready(())
+                }
+                std::task::Poll::Pending => std::task::Poll::Pending,
+            }
+        }));
     }
     /// Polls the Future to completion.
     ///
@@ -126,11 +413,9 @@ impl AsyncRuntime {
         self.runtime.block_on(f)
     }
 
-    /// Terminate the runtime and wait for all remaining tasks to complete.
+    /// Polls the Future to completion and returns its output along with how long the poll took.
     ///
-    /// The `terminate` method initiates a graceful shutdown of the runtime, giving all
-    /// active tasks a chance to complete before shutting down. If any task fails to complete
-    /// within the specified timeout duration, the runtime will forcibly shut down.
+    /// The wall-clock time is measured from just before entering the runtime to just after leaving it.
     ///
     /// # Examples
     ///
@@ -138,30 +423,2063 @@ impl AsyncRuntime {
     /// use thread_runner::{AsyncRuntime, AsyncFlavor};
     /// use std::time::Duration;
     ///
-    /// let runtime = AsyncRuntime::new(AsyncFlavor::AllThreads);
-    /// runtime.execute(async {
-    ///     println!("This will execute on a single thread runtime.");
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let (result, elapsed) = runtime.poll_timed(async {
+    ///     tokio::time::sleep(Duration::from_millis(100)).await;
+    ///     42
     /// });
-    /// runtime.terminate(Duration::from_secs(1));
+    /// assert_eq!(result, 42);
+    /// assert!(elapsed >= Duration::from_millis(100));
     /// ```
+    pub fn poll_timed<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+    ) -> (T, std::time::Duration) {
+        let started = std::time::Instant::now();
+        let output = self.runtime.block_on(f);
+        (output, started.elapsed())
+    }
+
+    /// Polls an already pinned Future to completion without taking ownership of it.
     ///
-    /// # Note
+    /// This is useful for futures that are `!Unpin` and must stay pinned at a stable address, since the
+    /// caller keeps the future in place rather than moving it into `poll`.
     ///
-    /// By default, the runtime will wait for all futures to complete before shutting down, which can be unnecessarily time-consuming in some situations. For these cases, it's best to use the `terminate` method to specify a timeout for the shutdown.
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let future = async { 42 };
+    /// tokio::pin!(future);
+    /// assert_eq!(runtime.poll_pin(future), 42);
+    /// ```
+    pub fn poll_pin<T>(
+        &self,
+        f: std::pin::Pin<&mut (dyn std::future::Future<Output = T> + Send)>,
+    ) -> T {
+        self.runtime.block_on(f)
+    }
 
-    pub fn terminate(self, timeout: std::time::Duration) {
-        self.runtime.shutdown_timeout(timeout)
+    /// Polls the Future to completion and delivers its output through the given oneshot sender.
+    ///
+    /// This integrates with channel-based code, e.g. when polling on a helper thread on behalf of another.
+    /// If the receiver was already dropped, the output is discarded and a message is logged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use tokio::sync::oneshot;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let (tx, rx) = oneshot::channel();
+    ///
+    /// std::thread::spawn(move || runtime.poll_into(async { 42 }, tx));
+    /// assert_eq!(rx.blocking_recv().unwrap(), 42);
+    /// ```
+    ///
+    /// A dropped receiver does not cause a panic:
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use tokio::sync::oneshot;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let (tx, rx) = oneshot::channel();
+    /// drop(rx);
+    ///
+    /// runtime.poll_into(async { 42 }, tx);
+    /// assert_eq!(runtime.poll(async { 7 }), 7);
+    /// ```
+    pub fn poll_into<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        tx: tokio::sync::oneshot::Sender<T>,
+    ) {
+        if tx.send(self.runtime.block_on(f)).is_err() {
+            diag::debug!("poll_into receiver dropped, discarding output");
+        }
     }
-}
 
-/// Specifies the type of Tokio runtime to create.
-pub enum AsyncFlavor {
-    /// Creates a Tokio runtime with a single thread.
-    CurrentThread,
+    /// Polls the Future to completion within a `tracing` span labeled with `name`.
+    ///
+    /// The future is instrumented with an `INFO` span named `poll_traced`, whose `name` field is set to
+    /// `name`, so downstream instrumentation shows the boundary of each top-level blocking call.
+    ///
+    /// Only available with the `tracing` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// # use std::sync::{Arc, Mutex};
+    /// # use tracing::field::{Field, Visit};
+    /// # use tracing::span::{Attributes, Id, Record};
+    /// # use tracing::{Event, Metadata, Subscriber};
+    /// #
+    /// # /// Records the `name` field of every span, and the ids of the spans entered
+    /// # #[derive(Clone, Default)]
+    /// # struct Recorder {
+    /// #     names: Arc<Mutex<Vec<String>>>,
+    /// #     entered: Arc<Mutex<Vec<u64>>>,
+    /// # }
+    /// #
+    /// # struct NameVisitor<'a>(&'a mut String);
+    /// #
+    /// # impl Visit for NameVisitor<'_> {
+    /// #     fn record_str(&mut self, field: &Field, value: &str) {
+    /// #         if field.name() == "name" {
+    /// #             *self.0 = value.to_string();
+    /// #         }
+    /// #     }
+    /// #     fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+    /// # }
+    /// #
+    /// # impl Subscriber for Recorder {
+    /// #     fn enabled(&self, _: &Metadata<'_>) -> bool {
+    /// #         true
+    /// #     }
+    /// #     fn new_span(&self, span: &Attributes<'_>) -> Id {
+    /// #         let mut name = String::new();
+    /// #         span.record(&mut NameVisitor(&mut name));
+    /// #         let mut names = self.names.lock().unwrap();
+    /// #         names.push(name);
+    /// #         Id::from_u64(names.len() as u64)
+    /// #     }
+    /// #     fn record(&self, _: &Id, _: &Record<'_>) {}
+    /// #     fn record_follows_from(&self, _: &Id, _: &Id) {}
+    /// #     fn event(&self, _: &Event<'_>) {}
+    /// #     fn enter(&self, span: &Id) {
+    /// #         self.entered.lock().unwrap().push(span.into_u64());
+    /// #     }
+    /// #     fn exit(&self, _: &Id) {}
+    /// # }
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let recorder = Recorder::default();
+    /// let entered = recorder.entered.clone();
+    ///
+    /// // Collect the spans entered while the future runs
+    /// let inside = tracing::subscriber::with_default(recorder.clone(), || {
+    ///     runtime.poll_traced("fetch", async move { entered.lock().unwrap().clone() })
+    /// });
+    ///
+    /// assert_eq!(*recorder.names.lock().unwrap(), ["fetch"]);
+    /// assert_eq!(inside, [1]);
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub fn poll_traced<T, F: std::future::Future<Output = T>>(&self, name: &str, f: F) -> T {
+        use tracing::Instrument;
 
-    /// Creates a Tokio runtime with all available threads.
-    AllThreads,
+        let span = tracing::info_span!("poll_traced", name);
+        self.runtime.block_on(f.instrument(span))
+    }
 
-    /// Creates a Tokio runtime with the specified number of worker threads.
-    WorkerThreads(usize),
+    /// Polls the Future to completion while invoking `beat` every `every`, until `f` completes.
+    ///
+    /// The heartbeat runs as a separate task on the runtime, which is aborted as soon as `f` completes or
+    /// panics, so a sync caller can update a progress display or feed a watchdog during a slow operation.
+    ///
+    /// # Panics
+    /// Panics if `every` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    /// let beats = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let counter = beats.clone();
+    /// let result = runtime.poll_with_heartbeat(
+    ///     async {
+    ///         tokio::time::sleep(Duration::from_millis(300)).await;
+    ///         42
+    ///     },
+    ///     Duration::from_millis(100),
+    ///     move || {
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///     },
+    /// );
+    ///
+    /// assert_eq!(result, 42);
+    /// assert!(beats.load(Ordering::SeqCst) >= 2);
+    /// ```
+    pub fn poll_with_heartbeat<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        every: std::time::Duration,
+        beat: impl Fn() + Send + 'static,
+    ) -> T {
+        assert!(!every.is_zero(), "Heartbeat period must be non-zero");
+        let _heartbeat = AbortOnDrop(self.runtime.spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            // The first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                beat();
+            }
+        }));
+        self.runtime.block_on(f)
+    }
+
+    /// Polls the Future to completion and pushes its output into the shared buffer.
+    ///
+    /// The lock is only taken once the output is ready. A poisoned lock is recovered from, since pushing
+    /// cannot leave the buffer in an inconsistent state.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let events = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// for i in 1..=3 {
+    ///     runtime.poll_push(&events, async move { i * 10 });
+    /// }
+    /// assert_eq!(*events.lock().unwrap(), [10, 20, 30]);
+    /// ```
+    pub fn poll_push<T, F: std::future::Future<Output = T>>(
+        &self,
+        buf: &std::sync::Arc<std::sync::Mutex<Vec<T>>>,
+        f: F,
+    ) {
+        let output = self.runtime.block_on(f);
+        buf.lock().unwrap_or_else(|e| e.into_inner()).push(output);
+    }
+
+    /// Polls the Future to completion, running `tick` on the polling thread each time the future would block.
+    ///
+    /// `tick` runs right after every poll of `f` that returns `Pending`, before the thread waits to be woken.
+    /// Unlike `poll_with_heartbeat`, it runs on the calling thread and only between polls, so it can borrow
+    /// local state, but it does not run while `f` keeps the thread busy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let mut ticks = 0;
+    ///
+    /// let result = runtime.poll_with_tick(
+    ///     async {
+    ///         tokio::task::yield_now().await;
+    ///         42
+    ///     },
+    ///     || ticks += 1,
+    /// );
+    ///
+    /// assert_eq!(result, 42);
+    /// assert!(ticks >= 1);
+    /// ```
+    pub fn poll_with_tick<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        mut tick: impl FnMut(),
+    ) -> T {
+        let mut f = std::pin::pin!(f);
+        self.runtime.block_on(std::future::poll_fn(|cx| {
+            let poll = f.as_mut().poll(cx);
+            if poll.is_pending() {
+                tick();
+            }
+            poll
+        }))
+    }
+
+    /// Polls the future created by `f` to completion, passing the progress it reports to `on_progress`.
+    ///
+    /// `f` receives the sending end of a channel for its progress events, which are handed to `on_progress` on
+    /// the polling thread, in order, between polls of the future. Every event sent before the future completes
+    /// is observed before this returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let mut observed = Vec::new();
+    ///
+    /// let size = runtime.poll_with_progress(
+    ///     |progress| async move {
+    ///         for percent in [25, 50, 100] {
+    ///             tokio::time::sleep(Duration::from_millis(10)).await;
+    ///             progress.send(percent).unwrap();
+    ///         }
+    ///         1024
+    ///     },
+    ///     |percent| observed.push(percent),
+    /// );
+    ///
+    /// assert_eq!(size, 1024);
+    /// assert_eq!(observed, [25, 50, 100]);
+    /// ```
+    pub fn poll_with_progress<P, T, F, Fut>(&self, f: F, on_progress: impl FnMut(P)) -> T
+    where
+        F: FnOnce(tokio::sync::mpsc::UnboundedSender<P>) -> Fut,
+        Fut: std::future::Future<Output = T>,
+    {
+        self.poll_states(f, on_progress)
+    }
+
+    /// Polls the state machine created by `f` to completion, passing every state it enters to `on_state`.
+    ///
+    /// This generalizes `poll_with_progress` to arbitrary state types: `f` receives the sending end of a channel
+    /// for its states, which are handed to `on_state` on the polling thread, in order, between polls of the
+    /// future. Every state sent before the future completes is observed before its final value is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// enum Upload {
+    ///     Start,
+    ///     Working { sent: usize },
+    ///     Done,
+    /// }
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    /// let mut observed = Vec::new();
+    ///
+    /// let etag = runtime.poll_states(
+    ///     |state| async move {
+    ///         state.send(Upload::Start).unwrap();
+    ///         tokio::time::sleep(Duration::from_millis(10)).await;
+    ///         state.send(Upload::Working { sent: 512 }).unwrap();
+    ///         tokio::time::sleep(Duration::from_millis(10)).await;
+    ///         state.send(Upload::Done).unwrap();
+    ///         "a1b2"
+    ///     },
+    ///     |state| observed.push(state),
+    /// );
+    ///
+    /// assert_eq!(etag, "a1b2");
+    /// assert_eq!(observed, [Upload::Start, Upload::Working { sent: 512 }, Upload::Done]);
+    /// ```
+    pub fn poll_states<S, T, F, Fut>(&self, f: F, mut on_state: impl FnMut(S)) -> T
+    where
+        F: FnOnce(tokio::sync::mpsc::UnboundedSender<S>) -> Fut,
+        Fut: std::future::Future<Output = T>,
+    {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut f = std::pin::pin!(f(sender));
+        self.runtime.block_on(std::future::poll_fn(|cx| {
+            let poll = f.as_mut().poll(cx);
+            while let std::task::Poll::Ready(Some(state)) = receiver.poll_recv(cx) {
+                on_state(state);
+            }
+            poll
+        }))
+    }
+
+    /// Polls the Future to completion, capturing the records it logs through the `log` crate.
+    ///
+    /// Only the records logged on the polling thread are captured, which includes everything logged by `f`
+    /// itself, but not by the tasks it spawns. This is intended for test binaries: it installs a capturing
+    /// logger as the global logger on first use, which drops the records of threads that are not capturing,
+    /// and does so only if no logger was installed before; otherwise nothing is captured. The max level of
+    /// `log` is raised to `Trace` while a capture runs, and restored once none does.
+    ///
+    /// Only available with the `log` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let (result, logs) = runtime.poll_capturing_logs(async {
+    ///     log::info!("fetched {} rows", 3);
+    ///     3
+    /// });
+    ///
+    /// assert_eq!(result, 3);
+    /// assert_eq!(logs.len(), 1);
+    /// assert_eq!(logs[0].level, log::Level::Info);
+    /// assert_eq!(logs[0].message, "fetched 3 rows");
+    /// ```
+    #[cfg(feature = "log")]
+    pub fn poll_capturing_logs<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+    ) -> (T, Vec<crate::execs::log_capture::CapturedLog>) {
+        crate::execs::log_capture::capture(|| self.runtime.block_on(f))
+    }
+
+    /// Polls the Future to completion, logging a warning if it is still pending after 30 seconds.
+    ///
+    /// See `poll_watched_with` for details.
+    pub fn poll_watched<T, F: std::future::Future<Output = T>>(&self, f: F) -> T {
+        self.poll_watched_with(f, WATCHDOG_THRESHOLD)
+    }
+
+    /// Polls the Future to completion, logging a warning if it is still pending after `threshold`.
+    ///
+    /// A future that waits on a task which the runtime has no spare thread to drive hangs `poll` silently.
+    /// This watchdog logs a single warning once `threshold` has elapsed, with the number of tasks alive on the
+    /// runtime, to help diagnosing such deadlocks. It is diagnostic only: `f` keeps being polled until it
+    /// completes. The warning goes through the `log` and `tracing` features that are enabled, and is dropped
+    /// otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "log")]
+    /// # mod recorder {
+    /// #     pub static WARNINGS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    /// #
+    /// #     pub struct Recorder;
+    /// #
+    /// #     impl log::Log for Recorder {
+    /// #         fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+    /// #             metadata.level() <= log::Level::Warn
+    /// #         }
+    /// #
+    /// #         fn log(&self, record: &log::Record<'_>) {
+    /// #             WARNINGS.lock().unwrap().push(record.args().to_string());
+    /// #         }
+    /// #
+    /// #         fn flush(&self) {}
+    /// #     }
+    /// # }
+    /// # #[cfg(feature = "log")]
+    /// # {
+    /// #     log::set_logger(&recorder::Recorder).unwrap();
+    /// #     log::set_max_level(log::LevelFilter::Warn);
+    /// # }
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// assert_eq!(runtime.poll_watched_with(async { 1 }, Duration::from_millis(50)), 1);
+    /// # #[cfg(feature = "log")]
+    /// # assert!(recorder::WARNINGS.lock().unwrap().is_empty());
+    ///
+    /// // Warns once the threshold has elapsed, and still waits for the future
+    /// let stuck = async { tokio::time::sleep(Duration::from_millis(200)).await };
+    /// runtime.poll_watched_with(stuck, Duration::from_millis(50));
+    /// # #[cfg(feature = "log")]
+    /// # {
+    /// #     let warnings = recorder::WARNINGS.lock().unwrap();
+    /// #     assert_eq!(warnings.len(), 1);
+    /// #     assert!(warnings[0].contains("still pending after 50ms"));
+    /// # }
+    /// ```
+    pub fn poll_watched_with<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        threshold: std::time::Duration,
+    ) -> T {
+        use futures::future::Either;
+
+        let metrics = self.runtime.metrics();
+        let watchdog = async move {
+            tokio::time::sleep(threshold).await;
+            diag::warning!(
+                "polled future still pending after {:?}, with {} tasks alive on the runtime; it may be deadlocked",
+                threshold,
+                metrics.num_alive_tasks(),
+            );
+            std::future::pending::<()>().await
+        };
+        self.runtime.block_on(async {
+            let f = std::pin::pin!(f);
+            let watchdog = std::pin::pin!(watchdog);
+            match futures::future::select(f, watchdog).await {
+                Either::Left((output, _)) => output,
+                Either::Right(((), _)) => unreachable!("the watchdog never completes"),
+            }
+        })
+    }
+
+    /// Polls the Future to completion, logging a warning whenever a single poll of it blocks longer than `budget`.
+    ///
+    /// A future doing blocking work, such as `std::thread::sleep` or synchronous IO, between two await points
+    /// holds its thread for the whole time, starving the other tasks of that thread. While `f` is polled, a
+    /// watchdog thread checks how long the current poll has been running, and logs a warning naming the blocked
+    /// thread once it exceeds `budget`, at most once per poll. This is a debugging aid: the blocking poll is not
+    /// interrupted. The warning goes through the `log` and `tracing` features that are enabled, and is dropped
+    /// otherwise.
+    ///
+    /// Only the polls of `f` itself are watched, not those of the tasks it spawns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "log")]
+    /// # mod recorder {
+    /// #     pub static WARNINGS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+    /// #
+    /// #     pub struct Recorder;
+    /// #
+    /// #     impl log::Log for Recorder {
+    /// #         fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+    /// #             metadata.level() <= log::Level::Warn
+    /// #         }
+    /// #
+    /// #         fn log(&self, record: &log::Record<'_>) {
+    /// #             WARNINGS.lock().unwrap().push(record.args().to_string());
+    /// #         }
+    /// #
+    /// #         fn flush(&self) {}
+    /// #     }
+    /// # }
+    /// # #[cfg(feature = "log")]
+    /// # {
+    /// #     log::set_logger(&recorder::Recorder).unwrap();
+    /// #     log::set_max_level(log::LevelFilter::Warn);
+    /// # }
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    /// let budget = Duration::from_millis(50);
+    ///
+    /// // Sleeping asynchronously yields the thread
+    /// let polite = async { tokio::time::sleep(Duration::from_millis(200)).await };
+    /// runtime.poll_detect_blocking(polite, budget);
+    /// # #[cfg(feature = "log")]
+    /// # assert!(recorder::WARNINGS.lock().unwrap().is_empty());
+    ///
+    /// // Blocking the thread is reported once, over the budget
+    /// let blocking = async { std::thread::sleep(Duration::from_millis(200)) };
+    /// runtime.poll_detect_blocking(blocking, budget);
+    /// # #[cfg(feature = "log")]
+    /// # {
+    /// #     let warnings = recorder::WARNINGS.lock().unwrap();
+    /// #     assert_eq!(warnings.len(), 1);
+    /// #     assert!(warnings[0].contains("without yielding"));
+    /// # }
+    /// ```
+    pub fn poll_detect_blocking<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        budget: std::time::Duration,
+    ) -> T {
+        use std::sync::mpsc::RecvTimeoutError;
+        use std::time::Instant;
+
+        let polling: std::sync::Mutex<Option<Instant>> = std::sync::Mutex::new(None);
+        let thread = std::thread::current();
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let check_every = (budget / 4).max(std::time::Duration::from_millis(1));
+        let mut f = std::pin::pin!(f);
+        std::thread::scope(|scope| {
+            let polling = &polling;
+            scope.spawn(move || {
+                let mut warned = None;
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(check_every) {
+                    let Some(started) = *polling.lock().unwrap() else {
+                        continue;
+                    };
+                    if warned != Some(started) && started.elapsed() > budget {
+                        warned = Some(started);
+                        diag::warning!(
+                            "future polled on thread {:?} has been running for {:?} without yielding, over its budget of {:?}",
+                            thread.name().unwrap_or("<unnamed>"),
+                            started.elapsed(),
+                            budget,
+                        );
+                    }
+                }
+            });
+            let output = self.runtime.block_on(std::future::poll_fn(|cx| {
+                *polling.lock().unwrap() = Some(Instant::now());
+                let poll = f.as_mut().poll(cx);
+                *polling.lock().unwrap() = None;
+                poll
+            }));
+            drop(stop);
+            output
+        })
+    }
+
+    /// Polls the Future to completion, unless called from within a runtime.
+    ///
+    /// `poll` panics when called from a thread that is already driving a tokio runtime, e.g. from
+    /// inside a future passed to `execute`. This variant returns an error instead, without polling `f`.
+    /// A thread that merely entered a runtime context, e.g. with `enter`, is not driving it, and polls
+    /// `f` as `poll` would.
+    ///
+    /// # Returns
+    /// - `Ok(T)` the Output of `f`
+    /// - `Err(NestedRuntimeError)` if the current thread is driving a runtime
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor, NestedRuntimeError};
+    /// use std::sync::Arc;
+    ///
+    /// let runtime = Arc::new(AsyncRuntime::new(AsyncFlavor::WorkerThreads(1)));
+    /// assert_eq!(runtime.poll_checked(async { 42 }), Ok(42));
+    ///
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    /// let nested = runtime.clone();
+    /// runtime.execute(async move {
+    ///     let result = nested.poll_checked(async { 42 });
+    ///     drop(nested);
+    ///     tx.send(result).unwrap();
+    /// });
+    /// assert_eq!(rx.recv().unwrap(), Err(NestedRuntimeError));
+    ///
+    /// // Entering the context of a runtime does not make polling nested
+    /// let _guard = runtime.enter();
+    /// assert_eq!(runtime.poll_checked(async { 42 }), Ok(42));
+    /// ```
+    pub fn poll_checked<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+    ) -> Result<T, NestedRuntimeError> {
+        // tokio only tells a nested `block_on` apart from an entered context by refusing to start
+        let polled =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.runtime.block_on(f)));
+        match polled {
+            Ok(output) => Ok(output),
+            Err(payload) if panic_message(payload.as_ref()).contains(NESTED_RUNTIME_PANIC) => {
+                Err(NestedRuntimeError)
+            }
+            Err(payload) => std::panic::resume_unwind(payload),
+        }
+    }
+
+    /// Polls the Future to completion, giving up once `timeout` has elapsed.
+    ///
+    /// # Returns
+    /// - `Ok(T)` the Output of `f` if it completed in time
+    /// - `Err(TimeoutError)` otherwise, in which case `f` is dropped
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor, TimeoutError};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let result = runtime.poll_timeout(async { 42 }, Duration::from_millis(50));
+    /// assert_eq!(result, Ok(42));
+    ///
+    /// let slow = async { tokio::time::sleep(Duration::from_millis(500)).await };
+    /// let result = runtime.poll_timeout(slow, Duration::from_millis(50));
+    /// assert_eq!(result, Err(TimeoutError));
+    /// ```
+    pub fn poll_timeout<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        timeout: std::time::Duration,
+    ) -> Result<T, TimeoutError> {
+        self.runtime
+            .block_on(async { tokio::time::timeout(timeout, f).await })
+            .map_err(TimeoutError::from)
+    }
+
+    /// Polls the Future to completion, returning `default` if it does not complete within `timeout`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    ///
+    /// let fast = async { "cached" };
+    /// assert_eq!(runtime.poll_timeout_or(fast, Duration::from_millis(50), "fallback"), "cached");
+    ///
+    /// let slow = async {
+    ///     tokio::time::sleep(Duration::from_millis(500)).await;
+    ///     "cached"
+    /// };
+    /// assert_eq!(runtime.poll_timeout_or(slow, Duration::from_millis(50), "fallback"), "fallback");
+    /// ```
+    pub fn poll_timeout_or<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        timeout: std::time::Duration,
+        default: T,
+    ) -> T {
+        self.poll_timeout(f, timeout).unwrap_or(default)
+    }
+
+    /// Polls the Future to completion, switching to the `fallback` future if it does not complete within `timeout`.
+    ///
+    /// Unlike `poll_timeout_or`, the fallback value is computed by a future, which is only polled once `f`
+    /// has timed out and been dropped. The fallback itself runs without a timeout.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    ///
+    /// let primary = async {
+    ///     tokio::time::sleep(Duration::from_millis(500)).await;
+    ///     "fresh"
+    /// };
+    /// let fallback = async { "stale" };
+    /// assert_eq!(runtime.poll_or_else(primary, Duration::from_millis(50), fallback), "stale");
+    ///
+    /// let result = runtime.poll_or_else(async { "fresh" }, Duration::from_millis(50), async { "stale" });
+    /// assert_eq!(result, "fresh");
+    /// ```
+    pub fn poll_or_else<T, F, G>(&self, f: F, timeout: std::time::Duration, fallback: G) -> T
+    where
+        F: std::future::Future<Output = T>,
+        G: std::future::Future<Output = T>,
+    {
+        self.runtime.block_on(async {
+            match tokio::time::timeout(timeout, f).await {
+                Ok(output) => output,
+                Err(_) => fallback.await,
+            }
+        })
+    }
+
+    /// Polls the Future to completion, running the `cleanup` future if it does not complete within `timeout`.
+    ///
+    /// On timeout, `f` is dropped first, then the future returned by `cleanup` is awaited, without a timeout,
+    /// before the error is returned. This gives resources held on behalf of `f` a chance to be released
+    /// asynchronously, such as by rolling back a transaction. `cleanup` is not called if `f` completes in time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor, TimeoutError};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let rolled_back = AtomicBool::new(false);
+    ///
+    /// let transaction = async {
+    ///     tokio::time::sleep(Duration::from_millis(500)).await;
+    ///     "committed"
+    /// };
+    /// let result = runtime.poll_timeout_with_cleanup(transaction, Duration::from_millis(50), || async {
+    ///     tokio::task::yield_now().await;
+    ///     rolled_back.store(true, Ordering::SeqCst);
+    /// });
+    /// assert_eq!(result, Err(TimeoutError));
+    /// assert!(rolled_back.load(Ordering::SeqCst));
+    /// ```
+    pub fn poll_timeout_with_cleanup<T, F, C, Fut>(
+        &self,
+        f: F,
+        timeout: std::time::Duration,
+        cleanup: C,
+    ) -> Result<T, TimeoutError>
+    where
+        F: std::future::Future<Output = T>,
+        C: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = ()>,
+    {
+        self.runtime.block_on(async {
+            match tokio::time::timeout(timeout, f).await {
+                Ok(output) => Ok(output),
+                Err(elapsed) => {
+                    cleanup().await;
+                    Err(TimeoutError::from(elapsed))
+                }
+            }
+        })
+    }
+
+    /// Polls the Future until it completes or `timeout` elapses, returning the results it pushed into `sink`.
+    ///
+    /// `f` pushes its results into `sink` as it progresses, so whatever it accumulated before the timeout is
+    /// kept even though `f` itself is dropped. The sink is drained once polling stops, and a poisoned lock is
+    /// recovered from.
+    ///
+    /// # Returns
+    /// - `(true, results)` if `f` completed in time
+    /// - `(false, results)` with the results pushed so far otherwise
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let sink = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// let out = sink.clone();
+    /// let slow = async move {
+    ///     for i in 0..10 {
+    ///         out.lock().unwrap().push(i);
+    ///         if i == 2 {
+    ///             tokio::time::sleep(Duration::from_secs(10)).await;
+    ///         }
+    ///     }
+    /// };
+    /// let (completed, results) = runtime.poll_partial(slow, Duration::from_millis(50), sink.clone());
+    /// assert!(!completed);
+    /// assert_eq!(results, [0, 1, 2]);
+    /// assert!(sink.lock().unwrap().is_empty());
+    /// ```
+    pub fn poll_partial<T, F: std::future::Future<Output = ()>>(
+        &self,
+        f: F,
+        timeout: std::time::Duration,
+        sink: std::sync::Arc<std::sync::Mutex<Vec<T>>>,
+    ) -> (bool, Vec<T>) {
+        let completed = self
+            .runtime
+            .block_on(async { tokio::time::timeout(timeout, f).await })
+            .is_ok();
+        let results = std::mem::take(&mut *sink.lock().unwrap_or_else(|e| e.into_inner()));
+        (completed, results)
+    }
+
+    /// Polls the Future to completion, giving up once the `deadline` is reached.
+    ///
+    /// This is the absolute counterpart of `poll_timeout`. If the deadline has already passed,
+    /// `f` is still polled once and its output is returned if it is immediately ready.
+    ///
+    /// # Returns
+    /// - `Ok(T)` the Output of `f` if it completed in time
+    /// - `Err(TimeoutError)` otherwise, in which case `f` is dropped
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor, TimeoutError};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let deadline = Instant::now() + Duration::from_millis(50);
+    /// let slow = async { tokio::time::sleep(Duration::from_millis(500)).await };
+    /// let result = runtime.poll_deadline(slow, deadline);
+    /// assert_eq!(result, Err(TimeoutError));
+    /// ```
+    pub fn poll_deadline<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        deadline: std::time::Instant,
+    ) -> Result<T, TimeoutError> {
+        let deadline = tokio::time::Instant::from_std(deadline);
+        self.runtime
+            .block_on(async { tokio::time::timeout_at(deadline, f).await })
+            .map_err(TimeoutError::from)
+    }
+
+    /// Polls the Future to completion, unless the `token` is cancelled first.
+    ///
+    /// The token can be cancelled from any thread, e.g. by a shutdown coordinator. When it is, `f` is
+    /// dropped at its current await point and the call returns immediately.
+    ///
+    /// # Returns
+    /// - `Some(T)` the Output of `f` if it completed
+    /// - `None` if the token was cancelled first
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor, CancelToken};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let token = CancelToken::new();
+    ///
+    /// let canceller = token.clone();
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(Duration::from_millis(50));
+    ///     canceller.cancel();
+    /// });
+    ///
+    /// let slow = async { tokio::time::sleep(Duration::from_secs(10)).await };
+    /// assert_eq!(runtime.poll_cancellable(slow, token), None);
+    /// ```
+    pub fn poll_cancellable<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        token: CancelToken,
+    ) -> Option<T> {
+        if token.is_cancelled() {
+            return None;
+        }
+        self.runtime.block_on(async {
+            let cancelled = token.cancelled();
+            match futures::future::select(std::pin::pin!(f), std::pin::pin!(cancelled)).await {
+                futures::future::Either::Left((output, _)) => Some(output),
+                futures::future::Either::Right(_) => None,
+            }
+        })
+    }
+
+    /// Polls the Future to completion, unless the tokio-util `token` is cancelled first.
+    ///
+    /// This is the counterpart of `poll_cancellable` for code already coordinated through
+    /// `tokio_util::sync::CancellationToken`. When the token is cancelled, `f` is dropped at its current
+    /// await point and the call returns immediately.
+    ///
+    /// Only available with the `tokio-util` feature.
+    ///
+    /// # Returns
+    /// - `Some(T)` the Output of `f` if it completed
+    /// - `None` if the token was cancelled first
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use tokio_util::sync::CancellationToken;
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// assert_eq!(runtime.poll_with_token(async { 42 }, CancellationToken::new()), Some(42));
+    ///
+    /// let token = CancellationToken::new();
+    /// let canceller = token.clone();
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(Duration::from_millis(50));
+    ///     canceller.cancel();
+    /// });
+    ///
+    /// let slow = async { tokio::time::sleep(Duration::from_secs(10)).await };
+    /// assert_eq!(runtime.poll_with_token(slow, token), None);
+    /// ```
+    #[cfg(feature = "tokio-util")]
+    pub fn poll_with_token<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        token: tokio_util::sync::CancellationToken,
+    ) -> Option<T> {
+        if token.is_cancelled() {
+            return None;
+        }
+        self.runtime.block_on(async {
+            let cancelled = token.cancelled();
+            match futures::future::select(std::pin::pin!(f), std::pin::pin!(cancelled)).await {
+                futures::future::Either::Left((output, _)) => Some(output),
+                futures::future::Either::Right(_) => None,
+            }
+        })
+    }
+
+    /// Polls the Future to completion, unless the `abort` flag is set first.
+    ///
+    /// This fits shutdown code already coordinated through an `AtomicBool`. Since a flag cannot wake the
+    /// runtime, it is checked every millisecond alongside `f`, which is dropped once the flag is set.
+    ///
+    /// # Returns
+    /// - `Some(T)` the Output of `f`
+    /// - `None` if `abort` was set before `f` completed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let abort = Arc::new(AtomicBool::new(false));
+    ///
+    /// assert_eq!(runtime.poll_until(async { 42 }, abort.clone()), Some(42));
+    ///
+    /// let flag = abort.clone();
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(Duration::from_millis(50));
+    ///     flag.store(true, Ordering::SeqCst);
+    /// });
+    /// let slow = async {
+    ///     tokio::time::sleep(Duration::from_secs(10)).await;
+    ///     42
+    /// };
+    /// assert_eq!(runtime.poll_until(slow, abort), None);
+    /// ```
+    pub fn poll_until<T, F: std::future::Future<Output = T>>(
+        &self,
+        f: F,
+        abort: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    ) -> Option<T> {
+        let aborted = || abort.load(std::sync::atomic::Ordering::Acquire);
+        if aborted() {
+            return None;
+        }
+        self.runtime.block_on(async {
+            let watch = async {
+                let mut interval = tokio::time::interval(std::time::Duration::from_millis(1));
+                while !aborted() {
+                    interval.tick().await;
+                }
+            };
+            match futures::future::select(std::pin::pin!(f), std::pin::pin!(watch)).await {
+                futures::future::Either::Left((output, _)) => Some(output),
+                futures::future::Either::Right(_) => None,
+            }
+        })
+    }
+
+    /// Polls the Future to completion, converting a panic or cancellation into an error.
+    ///
+    /// The future is spawned as a task on the runtime and the current thread blocks until it completes,
+    /// so a panic inside the future never unwinds into the caller.
+    ///
+    /// # Returns
+    /// - `Ok(T)` the Output of `f`
+    /// - `Err(RuntimeError::Panic)` if `f` panicked
+    /// - `Err(RuntimeError::Cancelled)` if `f` was cancelled before completing
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor, RuntimeError};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// assert_eq!(runtime.poll_try(async { 42 }), Ok(42));
+    ///
+    /// let result = runtime.poll_try(async {
+    ///     panic!("boom");
+    /// });
+    /// assert_eq!(result, Err::<(), _>(RuntimeError::Panic(String::from("boom"))));
+    /// ```
+    pub fn poll_try<T, F>(&self, f: F) -> Result<T, RuntimeError>
+    where
+        T: Send + 'static,
+        F: std::future::Future<Output = T> + Send + 'static,
+    {
+        self.runtime
+            .block_on(self.runtime.spawn(f))
+            .map_err(RuntimeError::from)
+    }
+
+    /// Polls the Future to completion, returning `default` if it panics or is cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// assert_eq!(runtime.poll_or(async { 42 }, 0), 42);
+    ///
+    /// let result = runtime.poll_or(async { panic!("boom") }, 0);
+    /// assert_eq!(result, 0);
+    /// ```
+    pub fn poll_or<T, F>(&self, f: F, default: T) -> T
+    where
+        T: Send + 'static,
+        F: std::future::Future<Output = T> + Send + 'static,
+    {
+        self.poll_try(f).unwrap_or(default)
+    }
+
+    /// Polls the future created by `f`, recreating and polling it again after `backoff` whenever it fails.
+    ///
+    /// `f` is called at most `attempts` times (and at least once), until its future resolves to `Ok`. The
+    /// backoff is awaited with `tokio::time::sleep`, so it does not block the runtime's worker threads.
+    ///
+    /// # Returns
+    /// - `Ok(T)` the output of the first successful attempt
+    /// - `Err(E)` the error of the last attempt, if all of them failed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let mut calls = 0;
+    ///
+    /// let result = runtime.poll_retry(5, Duration::from_millis(10), || {
+    ///     calls += 1;
+    ///     let attempt = calls;
+    ///     async move {
+    ///         if attempt < 3 {
+    ///             Err("unavailable")
+    ///         } else {
+    ///             Ok(attempt)
+    ///         }
+    ///     }
+    /// });
+    /// assert_eq!(result, Ok(3));
+    ///
+    /// let result = runtime.poll_retry(2, Duration::from_millis(1), || async { Err::<(), _>("down") });
+    /// assert_eq!(result, Err("down"));
+    /// ```
+    pub fn poll_retry<T, E, F, Fut>(
+        &self,
+        attempts: usize,
+        backoff: std::time::Duration,
+        mut f: F,
+    ) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, E>>,
+    {
+        self.runtime.block_on(async {
+            let mut attempt = 1;
+            loop {
+                match f().await {
+                    Err(_) if attempt < attempts => {
+                        attempt += 1;
+                        tokio::time::sleep(backoff).await;
+                    }
+                    result => return result,
+                }
+            }
+        })
+    }
+
+    /// Polls all the futures to completion within a single call to the runtime.
+    ///
+    /// The futures are driven concurrently, and their outputs are returned in the same order as `futures`.
+    /// Compared to calling `poll` in a loop, this enters and leaves the runtime only once, which avoids
+    /// the per-call overhead when polling many small futures.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Instant;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    ///
+    /// let start = Instant::now();
+    /// let naive: Vec<usize> = (0..10_000).map(|i| runtime.poll(async move { i * 2 })).collect();
+    /// let naive_elapsed = start.elapsed();
+    ///
+    /// let start = Instant::now();
+    /// let batched = runtime.poll_batch((0..10_000).map(|i| async move { i * 2 }).collect());
+    /// let batched_elapsed = start.elapsed();
+    ///
+    /// println!("poll loop: {naive_elapsed:?}, poll_batch: {batched_elapsed:?}");
+    /// assert_eq!(batched, naive);
+    /// ```
+    pub fn poll_batch<T, F: std::future::Future<Output = T>>(&self, futures: Vec<F>) -> Vec<T> {
+        use futures::StreamExt;
+
+        let ordered: futures::stream::FuturesOrdered<F> = futures.into_iter().collect();
+        self.runtime.block_on(ordered.collect())
+    }
+
+    /// Polls all the fallible futures concurrently, aborting the rest as soon as one of them fails.
+    ///
+    /// This gives `try_join_all` semantics to sync code: on the first `Err`, the remaining futures are dropped
+    /// without being polled to completion and that error is returned. Otherwise the outputs are returned in the
+    /// same order as `futures`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let finished = Arc::new(AtomicBool::new(false));
+    /// let third_finished = finished.clone();
+    ///
+    /// let futures: Vec<std::pin::Pin<Box<dyn std::future::Future<Output = Result<i32, &str>>>>> = vec![
+    ///     Box::pin(async { Ok(1) }),
+    ///     Box::pin(async {
+    ///         tokio::time::sleep(Duration::from_millis(10)).await;
+    ///         Err("second failed")
+    ///     }),
+    ///     Box::pin(async move {
+    ///         tokio::time::sleep(Duration::from_millis(200)).await;
+    ///         third_finished.store(true, Ordering::SeqCst);
+    ///         Ok(3)
+    ///     }),
+    /// ];
+    ///
+    /// assert_eq!(runtime.poll_try_join(futures), Err("second failed"));
+    /// assert!(!finished.load(Ordering::SeqCst));
+    ///
+    /// let all = runtime.poll_try_join((1..=3).map(|i| async move { Ok::<_, ()>(i) }).collect());
+    /// assert_eq!(all, Ok(vec![1, 2, 3]));
+    /// ```
+    pub fn poll_try_join<T, E, F>(&self, futures: Vec<F>) -> Result<Vec<T>, E>
+    where
+        F: std::future::Future<Output = Result<T, E>>,
+    {
+        self.runtime
+            .block_on(futures::future::try_join_all(futures))
+    }
+
+    /// Polls all the futures concurrently, returning the first output that satisfies `pred`.
+    ///
+    /// Outputs are checked in the order the futures complete. As soon as one matches, the remaining futures are
+    /// dropped without being polled to completion.
+    ///
+    /// # Returns
+    /// - `Some(output)` with the first output satisfying `pred`
+    /// - `None` if every future completed without a match
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let race = || {
+    ///     (1..10)
+    ///         .map(|i| async move {
+    ///             tokio::time::sleep(Duration::from_millis((10 - i) * 100)).await;
+    ///             i
+    ///         })
+    ///         .collect()
+    /// };
+    ///
+    /// let start = Instant::now();
+    /// let found = runtime.poll_until_any(race(), |x| *x > 5);
+    /// assert!(found.unwrap() > 5);
+    /// // The slowest future would only have finished after 900ms
+    /// assert!(start.elapsed() < Duration::from_millis(500));
+    ///
+    /// assert_eq!(runtime.poll_until_any(race(), |x| *x > 10), None);
+    /// ```
+    pub fn poll_until_any<T, F: std::future::Future<Output = T>>(
+        &self,
+        futures: Vec<F>,
+        pred: impl Fn(&T) -> bool,
+    ) -> Option<T> {
+        use futures::StreamExt;
+
+        let mut unordered: futures::stream::FuturesUnordered<F> = futures.into_iter().collect();
+        self.runtime.block_on(async {
+            while let Some(output) = unordered.next().await {
+                if pred(&output) {
+                    return Some(output);
+                }
+            }
+            None
+        })
+    }
+
+    /// Polls the named futures concurrently, returning the name and output of the first one to complete.
+    ///
+    /// The remaining futures are dropped without being polled to completion. When several futures are ready
+    /// at once, any of them may win.
+    ///
+    /// # Panics
+    /// Panics if `named` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let racer = |ms| async move {
+    ///     tokio::time::sleep(Duration::from_millis(ms)).await;
+    ///     ms
+    /// };
+    ///
+    /// let named = vec![(String::from("slow"), racer(500)), (String::from("fast"), racer(10))];
+    /// let (winner, output) = runtime.poll_select(named);
+    /// assert_eq!(winner, "fast");
+    /// assert_eq!(output, 10);
+    /// ```
+    pub fn poll_select<T, F: std::future::Future<Output = T>>(
+        &self,
+        named: Vec<(String, F)>,
+    ) -> (String, T) {
+        use futures::StreamExt;
+
+        assert!(!named.is_empty(), "Cannot select between 0 futures");
+        let mut racing: futures::stream::FuturesUnordered<_> = named
+            .into_iter()
+            .map(|(name, f)| async move { (name, f.await) })
+            .collect();
+        self.runtime
+            .block_on(racing.next())
+            .expect("There is at least one future")
+    }
+
+    /// Polls all the futures to completion within a single call to the runtime, each with its own timeout.
+    ///
+    /// Every future is given `per_timeout` to complete, counted from the start of the call, so slow futures
+    /// do not hold back the others. The results are returned in the same order as `futures`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor, TimeoutError};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    /// let delays = [10, 500, 20, 500];
+    /// let futures = delays
+    ///     .iter()
+    ///     .map(|&ms| async move {
+    ///         tokio::time::sleep(Duration::from_millis(ms)).await;
+    ///         ms
+    ///     })
+    ///     .collect();
+    ///
+    /// let results = runtime.poll_all_timeout(futures, Duration::from_millis(100));
+    /// assert_eq!(results, vec![Ok(10), Err(TimeoutError), Ok(20), Err(TimeoutError)]);
+    /// ```
+    pub fn poll_all_timeout<T, F: std::future::Future<Output = T>>(
+        &self,
+        futures: Vec<F>,
+        per_timeout: std::time::Duration,
+    ) -> Vec<Result<T, TimeoutError>> {
+        use futures::StreamExt;
+
+        self.runtime.block_on(async {
+            let ordered: futures::stream::FuturesOrdered<_> = futures
+                .into_iter()
+                .map(|f| tokio::time::timeout(per_timeout, f))
+                .collect();
+            ordered
+                .map(|result| result.map_err(TimeoutError::from))
+                .collect()
+                .await
+        })
+    }
+
+    /// Stops accepting new futures through `execute`, and waits up to `timeout` for the spawned ones to complete.
+    ///
+    /// This drains the runtime before a reload, without shutting it down. Futures passed to `execute` from now on
+    /// are dropped without being spawned, until `resume` is called. Futures passed to `poll` and its variants are
+    /// still accepted. The calling thread drives the runtime while it waits, so this also drains a
+    /// `AsyncFlavor::CurrentThread` runtime.
+    ///
+    /// # Returns
+    /// - `true` if every spawned task completed within `timeout`
+    /// - `false` if some were still alive when it elapsed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    /// let done = Arc::new(AtomicUsize::new(0));
+    ///
+    /// for _ in 0..3 {
+    ///     let done = done.clone();
+    ///     runtime.execute(async move {
+    ///         tokio::time::sleep(Duration::from_millis(100)).await;
+    ///         done.fetch_add(1, Ordering::SeqCst);
+    ///     });
+    /// }
+    ///
+    /// assert!(runtime.quiesce(Duration::from_secs(5)));
+    /// assert_eq!(done.load(Ordering::SeqCst), 3);
+    ///
+    /// // New work is rejected until the runtime resumes
+    /// let rejected = done.clone();
+    /// runtime.execute(async move {
+    ///     rejected.fetch_add(1, Ordering::SeqCst);
+    /// });
+    /// std::thread::sleep(Duration::from_millis(50));
+    /// assert_eq!(done.load(Ordering::SeqCst), 3);
+    ///
+    /// runtime.resume();
+    /// let accepted = done.clone();
+    /// runtime.execute(async move {
+    ///     accepted.fetch_add(1, Ordering::SeqCst);
+    /// });
+    /// assert!(runtime.quiesce(Duration::from_secs(5)));
+    /// assert_eq!(done.load(Ordering::SeqCst), 4);
+    ///
+    /// // A current-thread runtime is driven by the caller of `quiesce`
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// runtime.execute(async { tokio::time::sleep(Duration::from_millis(10)).await });
+    /// assert!(runtime.quiesce(Duration::from_millis(500)));
+    /// ```
+    pub fn quiesce(&self, timeout: std::time::Duration) -> bool {
+        self.quiesced
+            .store(true, std::sync::atomic::Ordering::Release);
+        let metrics = self.runtime.metrics();
+        // tokio has no notification for the last task completing, so the check backs off between rounds
+        let drained = async {
+            let mut backoff = std::time::Duration::from_millis(1);
+            while metrics.num_alive_tasks() > 0 {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(QUIESCE_MAX_BACKOFF);
+            }
+        };
+        // The timeout registers its timer when created, which needs the context of the runtime
+        self.runtime
+            .block_on(async { tokio::time::timeout(timeout, drained).await })
+            .is_ok()
+    }
+
+    /// Accepts futures through `execute` again after `quiesce`.
+    pub fn resume(&self) {
+        self.quiesced
+            .store(false, std::sync::atomic::Ordering::Release);
+    }
+
+    /// Terminate the runtime and wait for all remaining tasks to complete.
+    ///
+    /// The `terminate` method initiates a graceful shutdown of the runtime, giving all
+    /// active tasks a chance to complete before shutting down. If any task fails to complete
+    /// within the specified timeout duration, the runtime will forcibly shut down.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::AllThreads);
+    /// runtime.execute(async {
+    ///     println!("This will execute on a single thread runtime.");
+    /// });
+    /// runtime.terminate(Duration::from_secs(1));
+    /// ```
+    ///
+    /// # Note
+    ///
+    /// By default, the runtime will wait for all futures to complete before shutting down, which can be unnecessarily time-consuming in some situations. For these cases, it's best to use the `terminate` method to specify a timeout for the shutdown.
+    pub fn terminate(self, timeout: std::time::Duration) {
+        self.runtime.shutdown_timeout(timeout)
+    }
+
+    /// Terminates the runtime like `terminate`, and reports whether tasks were abandoned.
+    ///
+    /// The number of alive tasks is read just before the shutdown begins; the ones waiting at an await point
+    /// are dropped without completing. Shutting down takes the full `timeout` only if some tasks kept their
+    /// worker thread busy past it, which is reported as `timed_out`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    /// for _ in 0..3 {
+    ///     runtime.execute(async { std::thread::sleep(Duration::from_secs(2)) });
+    /// }
+    /// std::thread::sleep(Duration::from_millis(50));
+    ///
+    /// let stats = runtime.terminate_report(Duration::from_millis(50));
+    /// assert!(stats.alive_tasks > 0);
+    /// assert!(stats.timed_out);
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    /// let stats = runtime.terminate_report(Duration::from_secs(5));
+    /// assert_eq!(stats.alive_tasks, 0);
+    /// assert!(!stats.timed_out);
+    /// ```
+    pub fn terminate_report(self, timeout: std::time::Duration) -> ShutdownStats {
+        let alive_tasks = self.runtime.metrics().num_alive_tasks();
+        let started = std::time::Instant::now();
+        self.runtime.shutdown_timeout(timeout);
+        ShutdownStats {
+            alive_tasks,
+            timed_out: started.elapsed() >= timeout,
+        }
+    }
+
+    /// Offloads the blocking closure `f` to the given `ThreadPool`, returning a future resolving to its result.
+    ///
+    /// Unlike `block_in_place`, the work runs on a pool sized and controlled by the caller rather than on the
+    /// runtime's threads, so CPU-heavy closures never occupy a worker of the runtime. The returned
+    /// `TaskHandle` is awaited like any other future.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor, ThreadPool};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let pool = ThreadPool::new(2);
+    ///
+    /// let sum = runtime.poll(async {
+    ///     runtime.offload(&pool, || (1..=1_000_000u64).sum::<u64>()).await
+    /// });
+    /// assert_eq!(sum, 500_000_500_000);
+    /// pool.join();
+    /// ```
+    pub fn offload<T, F, Q>(&self, pool: &ThreadPool<Q>, f: F) -> TaskHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        pool.submit_handle(f)
+    }
+
+    /// Spawns the Future on the runtime, returning a guard that aborts it if dropped before it completes.
+    ///
+    /// This ties the lifetime of the task to a scope of sync code: an early return, e.g. through `?`, drops
+    /// the guard and aborts the task at its next await point. `PollGuard::wait` blocks for the result.
+    ///
+    /// On a `CurrentThread` runtime, the task only makes progress while the runtime is being polled, e.g.
+    /// inside `PollGuard::wait`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    /// assert_eq!(runtime.poll_guarded(async { 6 * 7 }).wait(), 42);
+    ///
+    /// let finished = Arc::new(AtomicBool::new(false));
+    /// let flag = finished.clone();
+    /// let guard = runtime.poll_guarded(async move {
+    ///     tokio::time::sleep(Duration::from_millis(100)).await;
+    ///     flag.store(true, Ordering::SeqCst);
+    /// });
+    ///
+    /// drop(guard);
+    /// std::thread::sleep(Duration::from_millis(300));
+    /// assert!(!finished.load(Ordering::SeqCst));
+    /// ```
+    pub fn poll_guarded<T, F>(&self, f: F) -> PollGuard<'_, T>
+    where
+        T: Send + 'static,
+        F: std::future::Future<Output = T> + Send + 'static,
+    {
+        PollGuard {
+            runtime: &self.runtime,
+            task: Some(self.runtime.spawn(f)),
+        }
+    }
+
+    /// Runs the blocking closure `f` from within a future without stalling the other tasks of the runtime.
+    ///
+    /// This delegates to `tokio::task::block_in_place`, which hands the other tasks of the current worker
+    /// thread over to another worker while `f` blocks. It is cheaper than spawning a blocking task for short
+    /// blocking calls, but is only supported by multi-threaded runtimes. Outside of any runtime, `f` simply
+    /// runs on the current thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CurrentThreadError` without running `f` if this runtime is a `AsyncFlavor::CurrentThread`
+    /// one, or if the calling thread is driving another runtime of that flavor, since neither has another
+    /// worker to hand the tasks over to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::AllThreads);
+    /// let result = runtime.poll(async {
+    ///     runtime.block_in_place(|| {
+    ///         std::thread::sleep(Duration::from_millis(10));
+    ///         42
+    ///     })
+    /// });
+    /// assert_eq!(result, Ok(42));
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// assert!(runtime.block_in_place(|| 42).is_err());
+    /// ```
+    pub fn block_in_place<T>(&self, f: impl FnOnce() -> T) -> Result<T, CurrentThreadError> {
+        use tokio::runtime::{Handle, RuntimeFlavor};
+
+        let current_thread =
+            |handle: &Handle| handle.runtime_flavor() == RuntimeFlavor::CurrentThread;
+        if current_thread(self.runtime.handle())
+            || Handle::try_current().is_ok_and(|handle| current_thread(&handle))
+        {
+            return Err(CurrentThreadError);
+        }
+        Ok(tokio::task::block_in_place(f))
+    }
+
+    /// Enters the runtime context on the current thread.
+    ///
+    /// While the returned guard is alive, code on this thread that expects an ambient
+    /// tokio runtime (e.g. `tokio::spawn`) will use this runtime. Dropping the guard exits the context.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// let handle = {
+    ///     let _guard = runtime.enter();
+    ///     tokio::spawn(async { 42 })
+    /// };
+    /// assert_eq!(runtime.poll(handle).unwrap(), 42);
+    /// ```
+    pub fn enter(&self) -> tokio::runtime::EnterGuard<'_> {
+        self.runtime.enter()
+    }
+
+    /// Blocks on a tokio task spawned elsewhere, such as by another library, until it completes.
+    ///
+    /// The task keeps running on the runtime it was spawned on, which must be driving it: a multi-threaded
+    /// runtime that is still alive, or this runtime. A task spawned on another current-thread runtime only
+    /// makes progress while that runtime is polled, so waiting for it here may block forever.
+    ///
+    /// # Returns
+    /// - `Ok(T)` the output of the task
+    /// - `Err(JoinError)` if the task panicked or was aborted
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    ///
+    /// // A task spawned on the runtime of another library
+    /// let library = tokio::runtime::Runtime::new().unwrap();
+    /// let handle = library.spawn(async { 42 });
+    /// assert_eq!(runtime.join_handle(handle).unwrap(), 42);
+    ///
+    /// let handle = {
+    ///     let _guard = runtime.enter();
+    ///     tokio::spawn(async { panic!("task failed") })
+    /// };
+    /// assert!(runtime.join_handle(handle).unwrap_err().is_panic());
+    /// ```
+    pub fn join_handle<T>(
+        &self,
+        h: tokio::task::JoinHandle<T>,
+    ) -> Result<T, tokio::task::JoinError> {
+        self.runtime.block_on(h)
+    }
+
+    /// Polls the future created by `f` to completion, handing it a `RuntimeHandle` for spawning sub-tasks.
+    ///
+    /// `poll` blocks the calling thread on the runtime, so calling it again from inside a future that is being
+    /// polled would block a thread the runtime relies on, possibly the only one; tokio detects this and panics.
+    /// Recursive algorithms should instead spawn their sub-problems as tasks through the handle and await them,
+    /// which lets the runtime run them in parallel on its workers while the parent waits without blocking.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor, RuntimeHandle};
+    /// use futures::future::BoxFuture;
+    ///
+    /// fn fib(rt: RuntimeHandle, n: u64) -> BoxFuture<'static, u64> {
+    ///     Box::pin(async move {
+    ///         if n < 2 {
+    ///             return n;
+    ///         }
+    ///         // One half runs as a separate task, possibly on another worker
+    ///         let left = rt.spawn(fib(rt.clone(), n - 1));
+    ///         let right = fib(rt.clone(), n - 2).await;
+    ///         left.await.unwrap() + right
+    ///     })
+    /// }
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(4));
+    /// assert_eq!(runtime.poll_recursive(|rt| fib(rt, 20)), 6765);
+    /// ```
+    pub fn poll_recursive<T, F, Fut>(&self, f: F) -> T
+    where
+        F: FnOnce(RuntimeHandle) -> Fut,
+        Fut: std::future::Future<Output = T>,
+    {
+        let handle = RuntimeHandle {
+            handle: self.runtime.handle().clone(),
+        };
+        self.runtime.block_on(f(handle))
+    }
+
+    /// Polls the Future to completion with preference over the background tasks of the runtime.
+    ///
+    /// `poll` runs `f` itself on the calling thread, but its timers and IO are driven by the workers of the
+    /// runtime, which `f` has to wait for while they are saturated by background tasks. This instead drives
+    /// `f` with a worker reserved for it: a thread of this runtime that runs no background task, so the
+    /// wake-ups of `f` are never queued behind them. The reserved worker is started on the first call and
+    /// shared by the later ones, and the tasks `f` spawns run on it, until the runtime is dropped.
+    ///
+    /// This has limits: awaiting a task spawned on the regular workers, e.g. by `execute`, still waits for
+    /// them, IO resources created outside of `poll_priority` are driven by the regular workers, and the
+    /// tasks spawned by concurrent priority polls share the single reserved worker. If the reserved worker
+    /// cannot be started, the failure is logged and `f` is polled like `poll` would.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::WorkerThreads(2));
+    ///
+    /// // Background work hogging every worker without yielding
+    /// for _ in 0..4 {
+    ///     runtime.execute(async { std::thread::sleep(Duration::from_millis(500)) });
+    /// }
+    /// std::thread::sleep(Duration::from_millis(20));
+    ///
+    /// let start = Instant::now();
+    /// let output = runtime.poll_priority(async {
+    ///     tokio::time::sleep(Duration::from_millis(10)).await;
+    ///     42
+    /// });
+    /// assert_eq!(output, 42);
+    /// assert!(start.elapsed() < Duration::from_millis(300));
+    /// ```
+    pub fn poll_priority<T, F: std::future::Future<Output = T>>(&self, f: F) -> T {
+        let reserved = self.reserved.get_or_init(|| {
+            ReservedWorker::start()
+                .inspect_err(|err| {
+                    diag::warning!(
+                        "failed to start the reserved worker ({err}), polling without it"
+                    )
+                })
+                .ok()
+        });
+        match reserved {
+            Some(reserved) => reserved.handle.block_on(f),
+            None => self.runtime.block_on(f),
+        }
+    }
+
+    /// Runs the given future on a dedicated thread with its own single-threaded runtime.
+    ///
+    /// The future does not share any worker thread with the runtimes of the process, which isolates it from
+    /// contention with their tasks (e.g. for a long-running event loop). The output of the future is discarded.
+    /// This is an associated function, since the future does not use any `AsyncRuntime`.
+    ///
+    /// # Returns
+    /// - `Ok(DedicatedHandle)` used to stop the future and its thread
+    /// - `Err(io::Error)` if the runtime could not be built or the thread could not be spawned, in which case
+    ///   the future is dropped without being polled
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::AsyncRuntime;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    /// let ticks = counter.clone();
+    ///
+    /// let handle = AsyncRuntime::spawn_dedicated(async move {
+    ///     loop {
+    ///         ticks.fetch_add(1, Ordering::SeqCst);
+    ///         tokio::time::sleep(Duration::from_millis(1)).await;
+    ///     }
+    /// })
+    /// .expect("the dedicated thread could not start");
+    ///
+    /// while counter.load(Ordering::SeqCst) == 0 {
+    ///     std::thread::sleep(Duration::from_millis(1));
+    /// }
+    /// handle.shutdown();
+    ///
+    /// let stopped_at = counter.load(Ordering::SeqCst);
+    /// std::thread::sleep(Duration::from_millis(20));
+    /// assert_eq!(counter.load(Ordering::SeqCst), stopped_at);
+    /// ```
+    pub fn spawn_dedicated<F: std::future::Future + Send + 'static>(
+        f: F,
+    ) -> std::io::Result<DedicatedHandle> {
+        let (shutdown, signal) = futures::channel::oneshot::channel::<()>();
+        // Built here rather than on the new thread, so that a failure is reported to the caller
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let thread = std::thread::Builder::new().spawn(move || {
+            runtime.block_on(futures::future::select(Box::pin(f), signal));
+        })?;
+        Ok(DedicatedHandle { shutdown, thread })
+    }
+}
+
+/// Configures an `AsyncRuntime` before creating it, created with `AsyncRuntime::builder`.
+///
+/// The hooks run on every thread the runtime starts, which includes its worker threads and the threads
+/// of its blocking pool, but not the threads calling `poll`. They are the place for per-thread setup
+/// such as thread-local allocators or signal masks.
+///
+/// # Examples
+///
+/// ```
+/// use thread_runner::{AsyncRuntime, AsyncFlavor};
+/// use std::collections::HashSet;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::{Arc, Barrier, Mutex};
+///
+/// let started = Arc::new(Mutex::new(HashSet::new()));
+/// let stopped = Arc::new(AtomicUsize::new(0));
+///
+/// let (on_start, on_stop) = (started.clone(), stopped.clone());
+/// let runtime = AsyncRuntime::builder(AsyncFlavor::WorkerThreads(3))
+///     .on_thread_start(move || {
+///         on_start.lock().unwrap().insert(std::thread::current().id());
+///     })
+///     .on_thread_stop(move || {
+///         on_stop.fetch_add(1, Ordering::SeqCst);
+///     })
+///     .build();
+///
+/// // Occupy every worker at once, so each of them has started
+/// let barrier = Arc::new(Barrier::new(3));
+/// let tasks: Vec<_> = (0..3)
+///     .map(|_| {
+///         let barrier = barrier.clone();
+///         async move { tokio::spawn(async move { barrier.wait(); }).await.unwrap() }
+///     })
+///     .collect();
+/// runtime.poll_batch(tasks);
+/// assert_eq!(started.lock().unwrap().len(), 3);
+///
+/// drop(runtime);
+/// assert_eq!(stopped.load(Ordering::SeqCst), 3);
+/// ```
+pub struct AsyncRuntimeBuilder {
+    builder: tokio::runtime::Builder,
+}
+
+impl AsyncRuntimeBuilder {
+    /// Registers a callback run on each thread started by the runtime, before it runs any task.
+    pub fn on_thread_start(mut self, f: impl Fn() + Send + Sync + 'static) -> Self {
+        self.builder.on_thread_start(f);
+        self
+    }
+
+    /// Registers a callback run on each thread started by the runtime, right before it exits.
+    pub fn on_thread_stop(mut self, f: impl Fn() + Send + Sync + 'static) -> Self {
+        self.builder.on_thread_stop(f);
+        self
+    }
+
+    /// Sets the stack size, in bytes, of the threads started by the runtime.
+    pub fn thread_stack_size(mut self, bytes: usize) -> Self {
+        self.builder.thread_stack_size(bytes);
+        self
+    }
+
+    /// Creates the configured `AsyncRuntime`, falling back to a `CurrentThread` runtime if it cannot be built.
+    ///
+    /// The failure is logged. The fallback runtime drives its futures on the threads calling `poll`, so it
+    /// works where no thread can be spawned, but the hooks and thread settings of the builder do not apply.
+    pub fn build(self) -> AsyncRuntime {
+        self.try_build().unwrap_or_else(|err| {
+            diag::warning!(
+                "failed to build the runtime ({err}), falling back to a current-thread runtime"
+            );
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            AsyncRuntime::from_runtime(rt)
+        })
+    }
+
+    /// Creates the configured `AsyncRuntime`, failing if it cannot be built.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    /// use tokio::runtime::{Handle, RuntimeFlavor};
+    ///
+    /// // No thread can be spawned with such a stack, which makes building the worker threads fail
+    /// let oversized = || AsyncRuntime::builder(AsyncFlavor::WorkerThreads(2)).thread_stack_size(1 << 60);
+    /// assert!(oversized().try_build().is_err());
+    ///
+    /// // `build` falls back to a working current-thread runtime instead
+    /// let runtime = oversized().build();
+    /// let flavor = runtime.poll(async { Handle::current().runtime_flavor() });
+    /// assert_eq!(flavor, RuntimeFlavor::CurrentThread);
+    /// assert_eq!(runtime.poll(async { 6 * 7 }), 42);
+    /// ```
+    pub fn try_build(mut self) -> std::io::Result<AsyncRuntime> {
+        // tokio panics rather than failing when it cannot spawn the worker threads
+        let built = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.builder.build()));
+        match built {
+            Ok(rt) => rt.map(AsyncRuntime::from_runtime),
+            Err(payload) => Err(std::io::Error::other(panic_message(payload.as_ref()))),
+        }
+    }
+}
+
+/// The worker of an `AsyncRuntime` reserved for `AsyncRuntime::poll_priority`
+///
+/// Its thread drives a single-threaded runtime, whose timers, IO and tasks thus never wait for the regular
+/// workers, while the futures polled with priority run on the threads polling them.
+struct ReservedWorker {
+    handle: tokio::runtime::Handle,
+    /// Dropped to stop the thread, which drops the tasks left on the runtime
+    stop: Option<futures::channel::oneshot::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl ReservedWorker {
+    fn start() -> std::io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let handle = runtime.handle().clone();
+        let (stop, stopped) = futures::channel::oneshot::channel::<()>();
+        let thread = std::thread::Builder::new().spawn(move || {
+            let _ = runtime.block_on(stopped);
+        })?;
+        Ok(Self {
+            handle,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for ReservedWorker {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A handle to a future running on its own thread, created by `AsyncRuntime::spawn_dedicated`.
+///
+/// Dropping the handle also stops the future, but without waiting for its thread to exit.
+pub struct DedicatedHandle {
+    shutdown: futures::channel::oneshot::Sender<()>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl DedicatedHandle {
+    /// Stops the future at its next await point and blocks until its thread has exited.
+    ///
+    /// # Panics
+    /// Panics if the future panicked, with the same payload.
+    pub fn shutdown(self) {
+        let _ = self.shutdown.send(());
+        if let Err(payload) = self.thread.join() {
+            std::panic::resume_unwind(payload);
+        }
+    }
+
+    /// Returns `true` if the future has completed or been stopped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+}
+
+/// A task spawned with `AsyncRuntime::poll_guarded`, aborted if the guard is dropped before it completes.
+pub struct PollGuard<'a, T> {
+    runtime: &'a tokio::runtime::Runtime,
+    task: Option<tokio::task::JoinHandle<T>>,
+}
+
+impl<T> PollGuard<'_, T> {
+    /// Blocks the current thread until the task has completed, and returns its result.
+    ///
+    /// # Panics
+    /// Panics if the task panicked, with the same payload.
+    pub fn wait(mut self) -> T {
+        let task = self.task.take().expect("the task is only taken by wait");
+        match self.runtime.block_on(task) {
+            Ok(output) => output,
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        }
+    }
+}
+
+impl<T> Drop for PollGuard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+/// Aborts a background task when dropped, including while unwinding from a panic
+struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// A clonable handle for spawning tasks on an `AsyncRuntime` from its own futures, passed by `AsyncRuntime::poll_recursive`.
+#[derive(Clone)]
+pub struct RuntimeHandle {
+    handle: tokio::runtime::Handle,
+}
+
+impl RuntimeHandle {
+    /// Spawns the future as a new task of the runtime, returning a handle that resolves to its output.
+    ///
+    /// Awaiting the handle yields `Err` if the task panicked or was cancelled.
+    pub fn spawn<F>(&self, f: F) -> tokio::task::JoinHandle<F::Output>
+    where
+        F: std::future::Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.handle.spawn(f)
+    }
+}
+
+/// What happened to the tasks of an `AsyncRuntime` shut down with `AsyncRuntime::terminate_report`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownStats {
+    /// The number of tasks that were alive when the shutdown began.
+    pub alive_tasks: usize,
+    /// Whether the shutdown timed out, abandoning the tasks that were still running.
+    pub timed_out: bool,
+}
+
+/// Specifies the type of Tokio runtime to create.
+pub enum AsyncFlavor {
+    /// Creates a Tokio runtime with a single thread.
+    CurrentThread,
+
+    /// Creates a Tokio runtime with all available threads.
+    AllThreads,
+
+    /// Creates a Tokio runtime with the specified number of worker threads.
+    WorkerThreads(usize),
+
+    /// Creates a Tokio runtime with the number of worker threads given by the `THREAD_RUNNER_WORKERS`
+    /// environment variable, or with all available threads if it is unset or invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use thread_runner::{AsyncRuntime, AsyncFlavor};
+    ///
+    /// let workers = |runtime: &AsyncRuntime| {
+    ///     runtime.poll(async { tokio::runtime::Handle::current().metrics().num_workers() })
+    /// };
+    /// let available = std::thread::available_parallelism().unwrap().get();
+    ///
+    /// std::env::set_var("THREAD_RUNNER_WORKERS", "3");
+    /// assert_eq!(workers(&AsyncRuntime::new(AsyncFlavor::FromEnv)), 3);
+    ///
+    /// std::env::set_var("THREAD_RUNNER_WORKERS", "many");
+    /// assert_eq!(workers(&AsyncRuntime::new(AsyncFlavor::FromEnv)), available);
+    ///
+    /// std::env::remove_var("THREAD_RUNNER_WORKERS");
+    /// assert_eq!(workers(&AsyncRuntime::new(AsyncFlavor::FromEnv)), available);
+    /// ```
+    FromEnv,
 }
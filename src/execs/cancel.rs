@@ -0,0 +1,61 @@
+//! This module contains CancelToken for cancelling work from another thread
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A clonable handle used to cancel work from any thread.
+///
+/// All the clones of a token share the same state, so calling `cancel` on one of them cancels the work
+/// observing any of the others. Once cancelled, a token stays cancelled.
+///
+/// # Example
+///
+/// ```
+/// use thread_runner::CancelToken;
+///
+/// let token = CancelToken::new();
+/// let observer = token.clone();
+///
+/// std::thread::spawn(move || token.cancel()).join().unwrap();
+/// assert!(observer.is_cancelled());
+/// ```
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<CancelState>,
+}
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: tokio::sync::Notify,
+}
+
+impl CancelToken {
+    /// Creates a new token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token, waking up everything waiting on `cancelled`.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Returns `true` if `cancel` has been called on this token or any of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Resolves once the token is cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            // Registering before checking the flag ensures a concurrent `cancel` is not missed
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! This module contains AsyncCondvar for signalling async code from sync threads
+
+use std::sync::Arc;
+
+/// A clonable condition variable that sync threads notify and futures await.
+///
+/// This bridges legacy code signalling with a `Condvar` to async code: `notify` is a plain method callable
+/// from any thread, while `wait` is awaited without blocking the runtime or busy-polling. Unlike a
+/// `Condvar`, a notification sent while no future is waiting is not lost: it is kept, and the next call to
+/// `wait` resolves right away. At most one such notification is kept, however many times `notify` was called.
+///
+/// # Example
+///
+/// ```
+/// use thread_runner::{AsyncCondvar, AsyncFlavor, AsyncRuntime};
+/// use std::time::{Duration, Instant};
+///
+/// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+/// let cv = AsyncCondvar::new();
+///
+/// let notifier = cv.clone();
+/// let started = Instant::now();
+/// let worker = std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_millis(50));
+///     notifier.notify();
+/// });
+///
+/// runtime.poll(async { cv.wait().await });
+/// assert!(started.elapsed() >= Duration::from_millis(50));
+/// worker.join().unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct AsyncCondvar {
+    notify: Arc<tokio::sync::Notify>,
+}
+
+impl AsyncCondvar {
+    /// Creates a new condition variable, with no notification kept.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wakes up one future waiting on `wait`, or lets the next call to `wait` resolve right away if none is.
+    pub fn notify(&self) {
+        self.notify.notify_one();
+    }
+
+    /// Wakes up every future currently waiting on `wait`.
+    ///
+    /// Unlike `notify`, nothing is kept when no future is waiting.
+    pub fn notify_all(&self) {
+        self.notify.notify_waiters();
+    }
+
+    /// Resolves once this condition variable, or any of its clones, is notified.
+    pub async fn wait(&self) {
+        self.notify.notified().await
+    }
+}
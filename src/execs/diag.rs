@@ -0,0 +1,34 @@
+//! This module contains the macros emitting the diagnostics of this crate
+//!
+//! Diagnostics go through the `log` and `tracing` features that are enabled, and are dropped otherwise, so
+//! that the crate never writes to the standard streams of the application on its own.
+
+/// Emits a diagnostic at the given level through the enabled logging features
+macro_rules! emit {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::$level!(target: "thread_runner", $($arg)+);
+        #[cfg(feature = "tracing")]
+        tracing::$level!(target: "thread_runner", $($arg)+);
+        #[cfg(not(any(feature = "log", feature = "tracing")))]
+        {
+            let _ = format_args!($($arg)+);
+        }
+    }};
+}
+
+/// Reports something unexpected that the crate recovered from
+macro_rules! warning {
+    ($($arg:tt)+) => {
+        $crate::execs::diag::emit!(warn, $($arg)+)
+    };
+}
+
+/// Reports a decision of the crate that is only of interest when debugging
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        $crate::execs::diag::emit!(debug, $($arg)+)
+    };
+}
+
+pub(crate) use {debug, emit, warning};
@@ -0,0 +1,26 @@
+//! This module contains helpers for configuring the pools from the environment
+
+use crate::execs::diag;
+
+/// The environment variable read by `AsyncFlavor::FromEnv` and `ThreadPool::from_env`
+pub const WORKERS_ENV_VAR: &str = "THREAD_RUNNER_WORKERS";
+
+/// Reads the number of workers from `THREAD_RUNNER_WORKERS`, falling back to `default`
+/// when the variable is unset or does not hold a positive integer
+pub(crate) fn workers_from_env(default: usize) -> usize {
+    let Ok(val) = std::env::var(WORKERS_ENV_VAR) else {
+        return default;
+    };
+    match val.trim().parse::<usize>() {
+        Ok(workers) if workers > 0 => {
+            diag::debug!("using {workers} workers from {WORKERS_ENV_VAR}");
+            workers
+        }
+        _ => {
+            diag::warning!(
+                "{WORKERS_ENV_VAR} holds {val:?}, not a positive integer; using {default} workers"
+            );
+            default
+        }
+    }
+}
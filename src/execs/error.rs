@@ -0,0 +1,140 @@
+//! This module contains the error types returned by this crate
+
+/// The reasons a future polled through `AsyncRuntime` can fail to produce its output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeError {
+    /// The future panicked, with the panic message if it carried one.
+    Panic(String),
+    /// The future was cancelled before it could complete, e.g. because the runtime shut down.
+    Cancelled,
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::Panic(msg) => write!(f, "future panicked: {msg}"),
+            RuntimeError::Cancelled => write!(f, "future was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+impl From<tokio::task::JoinError> for RuntimeError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        match err.try_into_panic() {
+            Ok(payload) => RuntimeError::Panic(panic_message(payload.as_ref())),
+            Err(_) => RuntimeError::Cancelled,
+        }
+    }
+}
+
+/// The error returned when a future polled through `AsyncRuntime` does not complete in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutError;
+
+impl std::fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "future did not complete before the timeout")
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+impl From<tokio::time::error::Elapsed> for TimeoutError {
+    fn from(_: tokio::time::error::Elapsed) -> Self {
+        TimeoutError
+    }
+}
+
+/// The error returned when polling a future from a thread that is already driving a tokio runtime.
+///
+/// Blocking on a future from inside a runtime would stall that runtime's worker thread, which is why
+/// tokio refuses to do it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NestedRuntimeError;
+
+impl std::fmt::Display for NestedRuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cannot poll a future from within a runtime; await it instead"
+        )
+    }
+}
+
+impl std::error::Error for NestedRuntimeError {}
+
+/// The error returned by `AsyncRuntime::block_in_place` on a runtime that cannot run blocking code in place.
+///
+/// Only multi-threaded runtimes can hand the other tasks of a worker over to another worker, so a
+/// `AsyncFlavor::CurrentThread` runtime has nowhere to move them while the closure blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrentThreadError;
+
+impl std::fmt::Display for CurrentThreadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cannot block in place on a current-thread runtime; spawn a blocking task instead"
+        )
+    }
+}
+
+impl std::error::Error for CurrentThreadError {}
+
+/// The reasons `ThreadPool::try_execute` can refuse a task, in which case the task is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryExecuteError {
+    /// The queue of the bounded pool is full.
+    Full,
+    /// The pool has been terminated, so the task would never run.
+    Halted,
+}
+
+impl std::fmt::Display for TryExecuteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TryExecuteError::Full => write!(f, "the queue of the pool is full"),
+            TryExecuteError::Halted => write!(f, "the pool has been terminated"),
+        }
+    }
+}
+
+impl std::error::Error for TryExecuteError {}
+
+/// The reasons `ThreadPool::schedule_dag` can reject a graph of tasks, before running any of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DagError {
+    /// Two nodes have the given id.
+    DuplicateId(String),
+    /// The node `id` lists a prerequisite that no node has.
+    UnknownPrerequisite { id: String, prerequisite: String },
+    /// The nodes with the given ids depend on each other in a cycle, possibly through other nodes.
+    Cycle(Vec<String>),
+}
+
+impl std::fmt::Display for DagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DagError::DuplicateId(id) => write!(f, "duplicate task id {id:?}"),
+            DagError::UnknownPrerequisite { id, prerequisite } => {
+                write!(f, "task {id:?} depends on unknown task {prerequisite:?}")
+            }
+            DagError::Cycle(ids) => write!(f, "tasks {ids:?} depend on each other in a cycle"),
+        }
+    }
+}
+
+impl std::error::Error for DagError {}
+
+/// Extracts the message of a panic payload, if it carries one
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
@@ -1,28 +1,103 @@
-use std::sync::mpsc::Receiver;
+//! This module contains ThreadPool and its helper types
 
-///! This module contains FixedThreadPool and its helper types
-///
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, Sender};
+use futures::stream::{FuturesUnordered, StreamExt};
+
+use crate::execs::cancel::CancelToken;
+use crate::execs::diag;
+use crate::execs::env::workers_from_env;
+use crate::execs::error::{panic_message, DagError, TryExecuteError};
+use crate::execs::latency::{LatencyReport, LatencyTracker};
+use crate::execs::threads::{self, WorkerThread};
+use crate::execs::timer;
 
 /// Describes the tasks that can be passed through the channels in `FixedThreadPool`
 type Job = Box<dyn Send + 'static + FnOnce()>;
 
+/// Describes the errors returned by the tasks passed to `ThreadPool::execute_checked`
+type TaskError = Box<dyn std::error::Error + Send>;
+
+/// Describes the futures that can be passed through the channels to be driven by a worker
+type LocalJob = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// How long a task submitted with `execute_yielding` runs before `Yielder::should_yield` asks it to yield
+const YIELD_SLICE: Duration = Duration::from_millis(1);
+
+/// How often the monitor of an autoscaling pool samples the depth of its queue
+const AUTOSCALE_INTERVAL: Duration = Duration::from_millis(50);
+
 /// sender is the `Sender` end of the channel used for passing tasks to the workers
 ///
 /// workers possess threads and are responsible for running the tasks they receiver from the channels in their own threads
 ///
-/// Each worker possesses a superficial clone of a single `Receiver` end that they borrow mutably through `parking_lot::Mutex` borrow
-
-pub struct ThreadPool {
-    sender: std::sync::mpsc::Sender<Msg>,
+/// The workers share the receiving end of a multi-consumer channel, which hands every message to exactly one
+/// of them. Receiving does not go through a lock held across the wait, so a worker that just finished a task
+/// cannot keep the others from picking up work:
+///
+/// ```
+/// use thread_runner::ThreadPool;
+/// use std::time::Duration;
+///
+/// let executor = ThreadPool::new(4);
+/// let group = executor.group();
+/// for _ in 0..400 {
+///     group.execute(|| std::thread::sleep(Duration::from_millis(1)));
+/// }
+/// group.wait();
+/// // A worker records a task once the task has returned
+/// executor.wait_for_idle_workers(4);
+///
+/// // Each worker ran its share of the tasks, give or take scheduling noise
+/// let stats = executor.per_worker_stats();
+/// assert_eq!(stats.iter().map(|stat| stat.tasks_run).sum::<usize>(), 400);
+/// for stat in stats {
+///     assert!((50..=150).contains(&stat.tasks_run), "{stat:?}");
+/// }
+/// executor.join();
+/// ```
+///
+/// The `Q` marker records whether the queue is `Unbounded` (the default) or `Bounded`, so that methods only
+/// meaningful for a bounded queue, such as `try_execute`, are only available on `ThreadPool<Bounded>`.
+pub struct ThreadPool<Q = Unbounded> {
+    submitter: Submitter,
     workers: Vec<Worker>,
+    restart: Option<Arc<RestartPolicy>>,
+    halted: Arc<AtomicBool>,
+    paused: Arc<PauseGate>,
+    idle: Arc<IdleWorkers>,
+    errors: Arc<std::sync::Mutex<Vec<TaskError>>>,
+    named: Arc<NamedTasks>,
+    budget: Option<Arc<Semaphore>>,
+    /// The shared channel of the workers, held weakly so that it still disconnects once they have all exited
+    receiver: std::sync::Weak<Receiver<Msg>>,
+    autoscaler: Option<Autoscaler>,
+    queue: std::marker::PhantomData<Q>,
 }
 
+/// Marks a `ThreadPool` whose queue grows without bound, created with `ThreadPool::unbounded`.
+pub struct Unbounded;
+
+/// Marks a `ThreadPool` whose queue holds a limited number of tasks, created with `ThreadPool::bounded`.
+pub struct Bounded;
+
 impl ThreadPool {
     /// Creates a new ThreadPool with the specified number of worker threads.
     ///
     /// The executor service will spawn `size` worker threads, each of which will
     /// process tasks submitted to the service using the `execute` method.
     ///
+    /// If the operating system fails to spawn some of the threads, e.g. on a system out of threads, the pool
+    /// logs the failure and runs with the workers it could spawn. Without any, it degrades to running every
+    /// task inline on the submitting thread, rather than crashing. `live_workers` tells how many were spawned.
+    ///
     /// # Arguments
     ///
     /// * `size` - The number of worker threads to create.
@@ -45,106 +120,4614 @@ impl ThreadPool {
     /// executor.join();
     /// ```
     ///
-
     pub fn new(size: usize) -> Self {
-        assert_ne!(size, 0, "Cannot create 0-sized thread pool");
-        let (sender, receiver) = std::sync::mpsc::channel();
-        let mut workers = Vec::with_capacity(size);
-        let receiver = Redex::new(receiver);
-        for _ in 0..size {
-            workers.push(Worker::new(receiver.clone()));
-        }
-        Self { sender, workers }
+        Self::unbounded(size)
     }
 
-    /// Executes the given closure as a `task` in a worker thread.
+    /// Creates a new ThreadPool with the specified number of worker threads and an unbounded queue.
     ///
-    /// This is achieved by sending the task to a pool of `workers`, who compete to execute it in their threads.
+    /// This is the same as `new`, but makes the queue semantics explicit at the call site.
     ///
-    /// Tasks submitted through the channel are executed in the order they are received (FIFO - First In, First Out).
-    /// This means if the tasks outnumber the workers, the later tasks are suspended until the earlier tasks are executed.
+    /// # Panics
+    ///
+    /// This function will panic if the value of size is equal to zero
+    ///
+    /// # Example
+    ///
+    /// ```compile_fail
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::unbounded(4);
+    /// // An unbounded queue is never full, so there is no `try_execute`
+    /// executor.try_execute(|| {});
+    /// ```
+    pub fn unbounded(size: usize) -> Self {
+        Self::spawn(PoolConfig::new(size))
+    }
+
+    /// Creates a new ThreadPool with the number of worker threads given by the `THREAD_RUNNER_WORKERS`
+    /// environment variable, or with `default` worker threads if it is unset or invalid.
+    ///
+    /// This allows sizing the pool at deployment time without recompiling.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the variable is unset or invalid and `default` is equal to zero
     ///
     /// # Example
     ///
     /// ```
     /// use thread_runner::ThreadPool;
     ///
-    /// let executor = ThreadPool::new(4);
+    /// for (var, expected) in [(Some("3"), 3), (Some("many"), 2), (None, 2)] {
+    ///     match var {
+    ///         Some(val) => std::env::set_var("THREAD_RUNNER_WORKERS", val),
+    ///         None => std::env::remove_var("THREAD_RUNNER_WORKERS"),
+    ///     }
+    ///     let executor = ThreadPool::from_env(2);
+    ///     assert_eq!(executor.size(), expected);
+    ///     executor.join();
+    /// }
+    /// ```
+    pub fn from_env(default: usize) -> Self {
+        Self::unbounded(workers_from_env(default))
+    }
+
+    /// Creates a new ThreadPool whose workers are replaced by fresh threads when a task panics.
     ///
-    /// for val in 0..10 {
-    ///     executor.execute(move || println!("{}", val));
+    /// When a task panics, the panic is logged and the worker's thread is replaced by a new one that
+    /// continues processing the queue. At most `max_restarts` replacements happen across the whole pool;
+    /// once the limit is exceeded, the panicking worker is not replaced and the pool is marked degraded.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the value of size is equal to zero
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::with_restart_on_panic(1, 3);
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    ///
+    /// for _ in 0..3 {
+    ///     executor.execute(|| panic!("task failed"));
     /// }
+    /// executor.execute(move || tx.send(()).unwrap());
+    /// rx.recv().unwrap();
     ///
+    /// assert_eq!(executor.restarts(), 3);
+    /// assert!(!executor.is_degraded());
+    ///
+    /// executor.execute(|| panic!("task failed"));
+    /// while !executor.is_degraded() {
+    ///     std::thread::sleep(std::time::Duration::from_millis(1));
+    /// }
+    /// assert_eq!(executor.restarts(), 3);
     /// executor.join();
     /// ```
+    pub fn with_restart_on_panic(size: usize, max_restarts: usize) -> Self {
+        Self::spawn(PoolConfig {
+            restart: Some(Arc::new(RestartPolicy::new(max_restarts))),
+            ..PoolConfig::new(size)
+        })
+    }
+
+    /// Creates a new ThreadPool whose worker threads have a stack of `stack_bytes` bytes.
     ///
-    /// # Note
+    /// This is useful for tasks that recurse deeply or keep large values on the stack, which could
+    /// overflow the default stack size of spawned threads.
     ///
-    /// If you want to wait for the submitted tasks to finish executing, you should call `join` on the executor service.
+    /// If the operating system fails to spawn the threads with the requested stack size, the pool degrades
+    /// like the one of `new` does when it runs out of threads.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the value of size is equal to zero
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// fn depth(n: u64) -> u64 {
+    ///     let frame = std::hint::black_box([n; 32]);
+    ///     if n == 0 { 0 } else { 1 + depth(n - 1) + frame[0] - n }
+    /// }
+    ///
+    /// let executor = ThreadPool::with_stack_size(2, 256 * 1024 * 1024);
+    /// let result = executor.submit(|| depth(200_000));
+    /// assert_eq!(result.recv().unwrap(), 200_000);
+    /// executor.join();
+    ///
+    /// // No thread can have a stack this large, so the pool runs its tasks inline instead
+    /// let executor = ThreadPool::with_stack_size(2, 1 << 60);
+    /// assert_eq!(executor.live_workers(), 0);
+    /// let ran_on = executor.submit(|| std::thread::current().id());
+    /// assert_eq!(ran_on.recv().unwrap(), std::thread::current().id());
+    /// executor.join();
+    /// ```
+    pub fn with_stack_size(size: usize, stack_bytes: usize) -> Self {
+        Self::spawn(PoolConfig {
+            stack_size: Some(stack_bytes),
+            ..PoolConfig::new(size)
+        })
+    }
 
-    pub fn execute<F: Send + 'static + FnOnce()>(&self, f: F) {
-        let msg = Msg::Task(Box::new(f));
-        self.sender.send(msg).unwrap()
+    /// Creates a new ThreadPool whose workers run inside the context of the tokio runtime behind `handle`.
+    ///
+    /// Each worker enters the runtime context for as long as it runs, so tasks offloaded from async code
+    /// can call `tokio::spawn`, create timers or use `Handle::current` to hand work back to the runtime.
+    /// The runtime must outlive the pool for the tasks to make use of it.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the value of size is equal to zero
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let runtime = tokio::runtime::Builder::new_multi_thread()
+    ///     .worker_threads(2)
+    ///     .build()
+    ///     .unwrap();
+    /// let executor = ThreadPool::with_runtime_context(2, runtime.handle().clone());
+    ///
+    /// let answer = executor.submit(|| {
+    ///     let task = tokio::spawn(async { 6 * 7 });
+    ///     futures::executor::block_on(task).unwrap()
+    /// });
+    /// assert_eq!(answer.recv().unwrap(), 42);
+    /// executor.join();
+    /// ```
+    pub fn with_runtime_context(size: usize, handle: tokio::runtime::Handle) -> Self {
+        Self::spawn(PoolConfig {
+            runtime: Some(handle),
+            ..PoolConfig::new(size)
+        })
     }
 
-    /// Blocks the current thread until the `ThreadPool` completes all its executions
+    /// Creates a new ThreadPool whose workers each own a random number generator, seeded deterministically.
     ///
-    pub fn join(self) {
-        for _ in 0..self.workers.len() {
-            self.sender.send(Msg::Terminate).unwrap();
-        }
+    /// The worker with index `i` seeds its `SmallRng` with `base_seed ^ i`, and tasks draw from the
+    /// generator of the worker running them with `with_rng`. The numbers drawn on each worker are thus the
+    /// same from one run to the next. Which tasks run on which worker is not, however: for reproducible
+    /// results, each worker should run the same share of the work, such as one task per worker. A worker
+    /// replaced by `with_restart_on_panic` starts over from its seed.
+    ///
+    /// Only available with the `rand` feature.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the value of size is equal to zero
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rand::Rng;
+    /// use std::sync::{Arc, Barrier};
+    /// use thread_runner::{with_rng, ThreadPool};
+    ///
+    /// fn estimate_pi(base_seed: u64) -> f64 {
+    ///     let executor = ThreadPool::with_seeded_rng(4, base_seed);
+    ///     // Every worker takes exactly one task, since each one waits for the others
+    ///     let barrier = Arc::new(Barrier::new(4));
+    ///     let hits: Vec<_> = (0..4)
+    ///         .map(|_| {
+    ///             let barrier = barrier.clone();
+    ///             executor.submit(move || {
+    ///                 barrier.wait();
+    ///                 with_rng(|rng| {
+    ///                     (0..10_000)
+    ///                         .filter(|_| rng.gen::<f64>().powi(2) + rng.gen::<f64>().powi(2) <= 1.0)
+    ///                         .count()
+    ///                 })
+    ///             })
+    ///         })
+    ///         .collect();
+    ///     let hits: usize = hits.iter().map(|hits| hits.recv().unwrap()).sum();
+    ///     executor.join();
+    ///     4.0 * hits as f64 / 40_000.0
+    /// }
+    ///
+    /// let pi = estimate_pi(42);
+    /// assert_eq!(pi, estimate_pi(42));
+    /// assert!((pi - std::f64::consts::PI).abs() < 0.1);
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn with_seeded_rng(size: usize, base_seed: u64) -> Self {
+        Self::spawn(PoolConfig {
+            seed: Some(base_seed),
+            ..PoolConfig::new(size)
+        })
+    }
 
-        for Worker { thread } in self.workers {
-            thread.join().unwrap();
-        }
+    /// Creates a new ThreadPool that limits the memory used by the tasks executed with `execute_sized` to `max_bytes`.
+    ///
+    /// Each such task declares an estimate of the bytes it needs, and only starts once that much of the budget is
+    /// available, so memory-heavy tasks cannot exhaust the memory by running all at once.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the value of size is equal to zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// const MB: usize = 1024 * 1024;
+    ///
+    /// let executor = ThreadPool::with_memory_budget(4, 100 * MB);
+    /// let running = Arc::new(AtomicUsize::new(0));
+    /// let max_running = Arc::new(AtomicUsize::new(0));
+    ///
+    /// for _ in 0..4 {
+    ///     let (running, max_running) = (running.clone(), max_running.clone());
+    ///     executor.execute_sized(60 * MB, move || {
+    ///         let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+    ///         max_running.fetch_max(now, Ordering::SeqCst);
+    ///         std::thread::sleep(Duration::from_millis(50));
+    ///         running.fetch_sub(1, Ordering::SeqCst);
+    ///     });
+    /// }
+    ///
+    /// executor.join();
+    /// assert_eq!(max_running.load(Ordering::SeqCst), 1);
+    /// ```
+    pub fn with_memory_budget(size: usize, max_bytes: usize) -> Self {
+        Self::spawn(PoolConfig {
+            memory_budget: Some(max_bytes),
+            ..PoolConfig::new(size)
+        })
     }
 
-    pub fn terminate(&self) {
-        for worker in self.workers.iter() {
-            worker.thread.thread().unpark();
-        }
+    /// Creates a new ThreadPool whose workers pick up queued tasks in the given `Order`.
+    ///
+    /// `Order::Fifo` is the default of the other constructors: tasks start in the order they were submitted,
+    /// so no task waits longer than the ones submitted after it. `Order::Lifo` starts the most recently
+    /// submitted task first, like a stack. Recursive workloads whose tasks submit follow-up tasks then finish
+    /// the most recent work first, which keeps the queue, and the memory held by its tasks, small, and tends
+    /// to reuse data that is still in the cache. The cost is fairness: under sustained load, early tasks may
+    /// wait for a long time.
+    ///
+    /// Futures submitted with `execute_future_local` and tasks routed by `execute_hinted` are not affected.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the value of size is equal to zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::{Order, ThreadPool};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// fn run_order(order: Order) -> Vec<usize> {
+    ///     let executor = ThreadPool::with_order(1, order);
+    ///     let ran = Arc::new(Mutex::new(Vec::new()));
+    ///
+    ///     // Keep the single worker busy until every task is queued
+    ///     let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    ///     executor.execute(move || release_rx.recv().unwrap());
+    ///     for i in 0..5 {
+    ///         let ran = ran.clone();
+    ///         executor.execute(move || ran.lock().unwrap().push(i));
+    ///     }
+    ///     release_tx.send(()).unwrap();
+    ///
+    ///     executor.join();
+    ///     let ran = ran.lock().unwrap().clone();
+    ///     ran
+    /// }
+    ///
+    /// assert_eq!(run_order(Order::Fifo), [0, 1, 2, 3, 4]);
+    /// assert_eq!(run_order(Order::Lifo), [4, 3, 2, 1, 0]);
+    /// ```
+    pub fn with_order(size: usize, order: Order) -> Self {
+        Self::spawn(PoolConfig {
+            order,
+            ..PoolConfig::new(size)
+        })
     }
-}
 
-/// A worker thread.
-///
-/// A `Worker` runs a loop that listens for tasks on a channel, and executes
-/// each task as it arrives. It terminates when it receives a `Msg::Terminate`
-/// message.
+    /// Creates a new ThreadPool whose workers borrow their threads from a process-wide registry.
+    ///
+    /// Each worker runs on an idle registry thread if there is one, and on a newly spawned thread otherwise.
+    /// Once the pool is joined, or dropped and drained, its threads go back to the registry for the next
+    /// shared pool, instead of exiting. Programs that create many short-lived pools thus spawn only as many
+    /// threads as their shared pools use at once.
+    ///
+    /// Registry threads never exit, and keep their thread-local values from one pool to the next. Since
+    /// they are spawned with the default stack size, this cannot be combined with `with_stack_size`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the value of size is equal to zero
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    /// for _ in 0..100 {
+    ///     let executor = ThreadPool::shared(4);
+    ///     for _ in 0..10 {
+    ///         let counter = counter.clone();
+    ///         executor.execute(move || {
+    ///             counter.fetch_add(1, Ordering::SeqCst);
+    ///         });
+    ///     }
+    ///     executor.join();
+    /// }
+    ///
+    /// assert_eq!(counter.load(Ordering::SeqCst), 1000);
+    /// // Every pool reused the threads of the previous one
+    /// assert_eq!(ThreadPool::threads_spawned(), 4);
+    /// ```
+    pub fn shared(size: usize) -> Self {
+        Self::spawn(PoolConfig {
+            shared: true,
+            ..PoolConfig::new(size)
+        })
+    }
 
-struct Worker {
-    thread: std::thread::JoinHandle<()>,
+    /// Creates a new ThreadPool that adds workers under sustained load and removes them once idle.
+    ///
+    /// The pool starts with `min` workers. A monitor thread samples the number of pending tasks every 50ms.
+    /// While it exceeds `target_queue_depth`, the number of workers is doubled, up to `max`. While the queue
+    /// is empty, one idle worker among the added ones is retired per sample, so that the original `min` workers
+    /// always remain. Workers busy with a task are never retired, and a retiring worker still runs the tasks
+    /// sent to it alone. `size` keeps returning `min`, while `live_workers` returns the current number of workers.
+    /// `per_worker_stats` only covers the first `min` workers. If the operating system fails to spawn the thread
+    /// monitoring the queue, the failure is logged and the pool keeps its `min` workers without scaling.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `min` is equal to zero or greater than `max`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let executor = ThreadPool::autoscaling(1, 8, 4);
+    /// assert_eq!(executor.live_workers(), 1);
+    ///
+    /// let wait_until = |condition: &dyn Fn() -> bool| {
+    ///     let deadline = Instant::now() + Duration::from_secs(5);
+    ///     while !condition() && Instant::now() < deadline {
+    ///         std::thread::sleep(Duration::from_millis(10));
+    ///     }
+    ///     condition()
+    /// };
+    ///
+    /// let burst = executor.group();
+    /// for _ in 0..400 {
+    ///     burst.execute(|| std::thread::sleep(Duration::from_millis(5)));
+    /// }
+    /// assert!(wait_until(&|| executor.live_workers() > 1));
+    /// burst.wait();
+    ///
+    /// // Once idle, the pool shrinks back to its minimum
+    /// assert!(wait_until(&|| executor.live_workers() == 1));
+    /// executor.join();
+    /// ```
+    pub fn autoscaling(min: usize, max: usize, target_queue_depth: usize) -> Self {
+        assert!(
+            min <= max,
+            "Cannot autoscale beyond a maximum below the minimum"
+        );
+        Self::spawn(PoolConfig {
+            autoscale: Some((max, target_queue_depth)),
+            ..PoolConfig::new(min)
+        })
+    }
+
+    /// Returns the number of worker threads spawned so far by all the pools of the process.
+    ///
+    /// This includes the threads replacing workers after a panic, and the threads added to the registry
+    /// of `shared` pools, but not the registry threads that were reused.
+    pub fn threads_spawned() -> usize {
+        threads::spawned()
+    }
 }
 
-impl Worker {
-    /// New workers loop continuously in their own threads until they receive a Terminate message from the channel
+impl ThreadPool<Bounded> {
+    /// Creates a new ThreadPool with the specified number of worker threads and a queue holding at most
+    /// `capacity` tasks that no worker has picked up yet.
     ///
-    /// This terminate message is useful for joining the individual `JoinHandle<()>` objects during `join` of `ThreadPool`
+    /// When the queue is full, `execute` blocks until a worker frees up a slot, while `try_execute` fails
+    /// immediately. With a `capacity` of zero, a task can only be submitted while a worker is idle.
     ///
-    /// Calling unwrap on `recv()` is safe in this case because the channel will never hang up
-    fn new(receiver: Redex<Receiver<Msg>>) -> Self {
-        Self {
-            thread: std::thread::spawn(move || loop {
-                let msg = receiver.recv().unwrap();
-                match msg {
-                    Msg::Terminate => break,
-                    Msg::Task(job) => job(),
-                }
-            }),
+    /// # Panics
+    ///
+    /// This function will panic if the value of size is equal to zero
+    pub fn bounded(size: usize, capacity: usize) -> Self {
+        Self::spawn(PoolConfig {
+            capacity: Some(capacity),
+            ..PoolConfig::new(size)
+        })
+    }
+
+    /// Executes the given closure as a `task` in a worker thread, unless the queue is full.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the task was queued
+    /// - `Err(TryExecuteError::Full)` if the queue is full, in which case `f` is dropped
+    /// - `Err(TryExecuteError::Halted)` if the pool has been terminated, in which case `f` is dropped
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::{ThreadPool, TryExecuteError};
+    ///
+    /// let executor = ThreadPool::bounded(1, 1);
+    /// let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    /// let (started_tx, started_rx) = std::sync::mpsc::channel();
+    ///
+    /// // Occupy the only worker, then fill the only slot of the queue
+    /// executor.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     release_rx.recv().unwrap();
+    /// });
+    /// started_rx.recv().unwrap();
+    /// assert_eq!(executor.try_execute(|| {}), Ok(()));
+    /// assert_eq!(executor.try_execute(|| {}), Err(TryExecuteError::Full));
+    ///
+    /// executor.terminate();
+    /// assert_eq!(executor.try_execute(|| {}), Err(TryExecuteError::Halted));
+    ///
+    /// release_tx.send(()).unwrap();
+    /// executor.join();
+    /// ```
+    pub fn try_execute<F: Send + 'static + FnOnce()>(&self, f: F) -> Result<(), TryExecuteError> {
+        if self.halted.load(Ordering::Acquire) {
+            return Err(TryExecuteError::Halted);
+        }
+        match self.submitter.try_send_msg(Msg::Task(Box::new(f))) {
+            Ok(()) => Ok(()),
+            Err(crossbeam_channel::TrySendError::Full(_)) => Err(TryExecuteError::Full),
+            Err(crossbeam_channel::TrySendError::Disconnected(_)) => {
+                panic!("All the workers of the pool have died")
+            }
         }
     }
 }
 
-/// Represents a message that can be sent through the executor's channel.
-enum Msg {
-    /// Instructs the worker to terminate its execution.
-    Terminate,
-    /// Represents a task to be executed by the worker.
-    Task(Job),
-}
+impl<Q> ThreadPool<Q> {
+    fn spawn(config: PoolConfig) -> Self {
+        assert_ne!(config.size, 0, "Cannot create 0-sized thread pool");
+        let (sender, receiver) = match config.capacity {
+            Some(capacity) => crossbeam_channel::bounded(capacity),
+            None => crossbeam_channel::unbounded(),
+        };
+        let mut workers = Vec::with_capacity(config.size);
+        let context = WorkerContext {
+            receiver: Arc::new(receiver),
+            restart: config.restart.clone(),
+            pending: Arc::new(AtomicUsize::new(0)),
+            stack_size: config.stack_size,
+            shared: config.shared,
+            runtime: config.runtime.clone(),
+            halted: Arc::new(AtomicBool::new(false)),
+            paused: Arc::default(),
+            idle: Arc::new(IdleWorkers::default()),
+            live: Arc::new(AtomicUsize::new(0)),
+            deadlines: Arc::default(),
+            labeled: Arc::default(),
+            priorities: Arc::default(),
+            retire: None,
+            #[cfg(feature = "rand")]
+            seed: config.seed,
+            stack: match config.order {
+                Order::Fifo => None,
+                Order::Lifo => Some(Arc::default()),
+            },
+        };
+        for _ in 0..config.size {
+            workers.push(Worker::new(context.clone(), workers.len()));
+        }
+        let autoscaler = config.autoscale.and_then(|(max, target_queue_depth)| {
+            Autoscaler::start(context.clone(), config.size, max, target_queue_depth)
+        });
+        Self {
+            submitter: Submitter {
+                sender,
+                pending: context.pending,
+                in_flight: Arc::new(InFlight::default()),
+                high_water: Arc::new(HighWaterHooks::default()),
+                latency: None,
+                stack: context.stack.clone(),
+                live: context.live.clone(),
+                halted: context.halted.clone(),
+                deadlines: context.deadlines.clone(),
+                labeled: context.labeled.clone(),
+                priorities: context.priorities.clone(),
+            },
+            workers,
+            restart: config.restart,
+            halted: context.halted,
+            paused: context.paused,
+            idle: context.idle,
+            errors: Arc::default(),
+            named: Arc::default(),
+            budget: config
+                .memory_budget
+                .map(|max| Arc::new(Semaphore::new(max))),
+            receiver: Arc::downgrade(&context.receiver),
+            autoscaler,
+            queue: std::marker::PhantomData,
+        }
+    }
 
-///
-///
+    /// Moves the pool behind a clonable `SharedThreadPool`, which joins it once the last clone is dropped.
+    ///
+    /// See `SharedThreadPool` for details.
+    pub fn into_shared(self) -> SharedThreadPool<Q> {
+        SharedThreadPool {
+            inner: Arc::new(SharedInner { pool: Some(self) }),
+        }
+    }
+
+    /// Returns a clonable handle for submitting tasks to this pool.
+    ///
+    /// Unlike the pool itself, a `Submitter` can be captured by the tasks running on the pool, which
+    /// lets a task enqueue follow-up work from inside a worker thread. See `Submitter` for details.
+    pub fn submitter(&self) -> Submitter {
+        self.submitter.clone()
+    }
+
+    /// Creates a new group of tasks that can be waited on independently of the rest of the pool's work.
+    ///
+    /// See `TaskGroup` for details.
+    pub fn group(&self) -> TaskGroup {
+        TaskGroup {
+            submitter: self.submitter.clone(),
+            tasks: Arc::new(InFlight::default()),
+        }
+    }
+
+    /// Creates a new root cancellation scope, under which tasks and their children can be cancelled together.
+    ///
+    /// See `CancelScope` for details.
+    pub fn scope(&self) -> CancelScope {
+        CancelScope {
+            submitter: self.submitter.clone(),
+            tokens: Arc::from([CancelToken::new()]),
+        }
+    }
+
+    /// Creates a new batch of tasks that can be waited on once, without consuming the pool.
+    ///
+    /// See `Batch` for details.
+    pub fn batch(&self) -> Batch {
+        Batch(self.group())
+    }
+
+    /// Registers a callback invoked whenever a submission pushes the number of pending tasks past `threshold`.
+    ///
+    /// The callback receives the number of pending tasks, and only fires when the queue crosses the threshold
+    /// rather than on every submission above it. It fires again if the queue drops back to the threshold and
+    /// crosses it anew. The callback runs on the submitting thread, so it should return quickly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// let (depth_tx, depth_rx) = std::sync::mpsc::channel();
+    /// executor.on_high_water(3, move |depth| depth_tx.send(depth).unwrap());
+    ///
+    /// let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    /// let (started_tx, started_rx) = std::sync::mpsc::channel();
+    /// executor.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     release_rx.recv().unwrap();
+    /// });
+    /// started_rx.recv().unwrap();
+    ///
+    /// for _ in 0..6 {
+    ///     executor.execute(|| {});
+    /// }
+    ///
+    /// let depths: Vec<usize> = depth_rx.try_iter().collect();
+    /// assert_eq!(depths, vec![4]);
+    ///
+    /// release_tx.send(()).unwrap();
+    /// executor.join();
+    /// ```
+    pub fn on_high_water(&self, threshold: usize, cb: impl Fn(usize) + Send + Sync + 'static) {
+        self.submitter.high_water.register(threshold, cb);
+    }
+
+    /// Returns the number of worker threads of the pool.
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Returns the number of workers whose thread is still running.
+    ///
+    /// Workers are lost when a task panics in a pool without a restart policy, or once the restart limit
+    /// is reached. A worker being replaced after a panic keeps counting as live until its replacement does.
+    /// When no live worker is left, tasks and futures are run inline on the submitting thread instead of
+    /// being queued, since nothing would ever pick them up, so submitting never hangs and `join` still returns.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// let executor = ThreadPool::with_restart_on_panic(1, 0);
+    /// assert_eq!(executor.live_workers(), 1);
+    ///
+    /// executor.execute(|| panic!("worker lost"));
+    /// while executor.live_workers() > 0 {
+    ///     std::thread::sleep(Duration::from_millis(1));
+    /// }
+    /// assert!(executor.is_degraded());
+    ///
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    /// executor.execute(move || tx.send(std::thread::current().id()).unwrap());
+    /// assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(std::thread::current().id()));
+    /// executor.join();
+    /// ```
+    pub fn live_workers(&self) -> usize {
+        self.submitter.live.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of tasks and futures submitted to the pool that no worker has picked up yet.
+    ///
+    /// Tasks discarded by `terminate` or `abort_pending` no longer count as pending.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// executor.wait_for_idle_workers(1);
+    ///
+    /// // The worker may take the task, but waits for the pool to resume before running it
+    /// executor.pause();
+    /// executor.execute(|| unreachable!());
+    /// assert_eq!(executor.pending(), 1);
+    ///
+    /// executor.terminate();
+    /// let deadline = Instant::now() + Duration::from_secs(5);
+    /// while executor.live_workers() > 0 && Instant::now() < deadline {
+    ///     std::thread::sleep(Duration::from_millis(1));
+    /// }
+    /// assert_eq!(executor.pending(), 0);
+    /// executor.join();
+    /// ```
+    pub fn pending(&self) -> usize {
+        self.submitter.pending.load(Ordering::Acquire)
+    }
+
+    /// Returns a snapshot of how much work each worker has done so far.
+    ///
+    /// Since workers compete for tasks on a shared queue, the work is not necessarily spread evenly, and
+    /// these statistics help diagnosing such imbalance. Tasks that panicked are not counted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let group = executor.group();
+    ///
+    /// for val in 0..50 {
+    ///     group.execute(move || std::thread::sleep(Duration::from_millis(val % 5)));
+    /// }
+    /// group.wait();
+    /// // A worker records a task once the task has returned
+    /// executor.wait_for_idle_workers(4);
+    ///
+    /// let stats = executor.per_worker_stats();
+    /// assert_eq!(stats.len(), 4);
+    /// assert_eq!(stats.iter().map(|stat| stat.tasks_run).sum::<usize>(), 50);
+    /// executor.join();
+    /// ```
+    pub fn per_worker_stats(&self) -> Vec<WorkerStat> {
+        self.workers
+            .iter()
+            .map(|worker| worker.stats.snapshot())
+            .collect()
+    }
+
+    /// Returns the fraction of time the workers have spent busy rather than waiting for work.
+    ///
+    /// This is computed as busy / (busy + idle) over all the workers, where the idle time includes the waits
+    /// still in progress, so a pool that stops receiving work sees its utilization decay. It is `0.0` before
+    /// any work has been received. A low utilization signals that the pool has more workers than its workload needs, while a
+    /// utilization close to `1.0` signals that tasks are queuing up.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// // Sparse work leaves the workers mostly idle
+    /// let executor = ThreadPool::new(2);
+    /// for _ in 0..5 {
+    ///     std::thread::sleep(Duration::from_millis(20));
+    ///     executor.execute(|| {});
+    /// }
+    /// std::thread::sleep(Duration::from_millis(20));
+    /// assert!(executor.utilization() < 0.5);
+    /// executor.join();
+    ///
+    /// // Saturating work keeps them busy
+    /// let executor = ThreadPool::new(2);
+    /// let group = executor.group();
+    /// for _ in 0..20 {
+    ///     group.execute(|| std::thread::sleep(Duration::from_millis(10)));
+    /// }
+    /// group.wait();
+    /// assert!(executor.utilization() > 0.8);
+    ///
+    /// // Then drops once the work stops
+    /// std::thread::sleep(Duration::from_millis(300));
+    /// assert!(executor.utilization() < 0.5);
+    /// executor.join();
+    /// ```
+    pub fn utilization(&self) -> f64 {
+        let (busy, idle) = self
+            .per_worker_stats()
+            .iter()
+            .fold((Duration::ZERO, Duration::ZERO), |(busy, idle), stat| {
+                (busy + stat.time_busy, idle + stat.time_idle)
+            });
+        let total = (busy + idle).as_secs_f64();
+        if total == 0.0 {
+            0.0
+        } else {
+            busy.as_secs_f64() / total
+        }
+    }
+
+    /// Blocks the current thread until at least `n` workers are idle, waiting for work.
+    ///
+    /// This lets a producer throttle itself before submitting a large batch, instead of oversubscribing
+    /// the pool. A worker that is still driving futures submitted with `execute_future_local` is not idle.
+    /// If `n` exceeds the number of live workers, this returns immediately, since the wait could never end,
+    /// and so does a wait that workers exiting meanwhile, e.g. after a panic, make impossible. The workers
+    /// added by an autoscaling pool count as long as they are live.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::{Arc, Barrier};
+    /// use std::time::Duration;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let started = Arc::new(Barrier::new(5));
+    /// let done = Arc::new(AtomicUsize::new(0));
+    ///
+    /// for i in 0..4 {
+    ///     let (started, done) = (started.clone(), done.clone());
+    ///     executor.execute(move || {
+    ///         started.wait();
+    ///         std::thread::sleep(Duration::from_millis(50 * (i + 1)));
+    ///         done.fetch_add(1, Ordering::SeqCst);
+    ///     });
+    /// }
+    ///
+    /// started.wait();
+    /// executor.wait_for_idle_workers(2);
+    /// assert!(done.load(Ordering::SeqCst) >= 2);
+    /// executor.join();
+    ///
+    /// // A lost worker can never become idle, so waiting for it returns
+    /// let executor = ThreadPool::with_restart_on_panic(2, 0);
+    /// let (started_tx, started_rx) = std::sync::mpsc::channel();
+    /// executor.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     panic!("worker lost");
+    /// });
+    /// started_rx.recv().unwrap();
+    /// executor.wait_for_idle_workers(2);
+    /// assert_eq!(executor.live_workers(), 1);
+    /// executor.join();
+    /// ```
+    pub fn wait_for_idle_workers(&self, n: usize) {
+        self.idle.wait_for(n, &self.submitter.live);
+    }
+
+    /// Enables recording the queue and run latency of every task submitted from now on.
+    ///
+    /// This is meant to be chained right after creating the pool, since the handles returned by `submitter`,
+    /// `group` and `batch` before enabling it do not record their tasks. Futures are not recorded.
+    /// Read the results with `latency_percentiles`.
+    pub fn with_latency_tracking(mut self) -> Self {
+        self.submitter.latency = Some(Arc::new(LatencyTracker::default()));
+        self
+    }
+
+    /// Returns the percentiles of the queue and run latency of the tasks completed so far.
+    ///
+    /// Queue latency is the time from submission until a worker starts running the task, and grows under
+    /// contention. Run latency is the time the task itself took. If latency tracking was not enabled with
+    /// `with_latency_tracking`, the report is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// let executor = ThreadPool::new(2).with_latency_tracking();
+    /// let group = executor.group();
+    ///
+    /// for _ in 0..20 {
+    ///     group.execute(|| std::thread::sleep(Duration::from_millis(5)));
+    /// }
+    /// group.wait();
+    /// // A task is recorded once it has returned
+    /// executor.wait_for_idle_workers(2);
+    ///
+    /// let report = executor.latency_percentiles();
+    /// assert_eq!(report.samples, 20);
+    /// assert!(report.run.p50 >= Duration::from_millis(5));
+    /// assert!(report.run.p99 < Duration::from_millis(100));
+    /// assert!(report.run.p50 <= report.run.p95 && report.run.p95 <= report.run.p99);
+    /// // 20 tasks on 2 workers queue up behind each other
+    /// assert!(report.queue.p99 >= Duration::from_millis(20));
+    /// executor.join();
+    /// ```
+    pub fn latency_percentiles(&self) -> LatencyReport {
+        self.submitter
+            .latency
+            .as_ref()
+            .map(|latency| latency.report())
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of workers that have been replaced after a panic.
+    ///
+    /// This is always zero for pools not created with `with_restart_on_panic`.
+    pub fn restarts(&self) -> usize {
+        self.restart
+            .as_ref()
+            .map_or(0, |policy| policy.restarts.load(Ordering::Acquire))
+    }
+
+    /// Returns `true` if a worker has died after the pool ran out of restarts.
+    pub fn is_degraded(&self) -> bool {
+        self.restart
+            .as_ref()
+            .is_some_and(|policy| policy.degraded.load(Ordering::Acquire))
+    }
+
+    /// Executes the given closure as a `task` in a worker thread.
+    ///
+    /// This is achieved by sending the task to a pool of `workers`, who compete to execute it in their threads.
+    ///
+    /// Tasks submitted through the channel are executed in the order they are received (FIFO - First In, First Out),
+    /// unless the pool was created with `with_order` and `Order::Lifo`.
+    /// This means if the tasks outnumber the workers, the later tasks are suspended until the earlier tasks are executed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::new(4);
+    ///
+    /// for val in 0..10 {
+    ///     executor.execute(move || println!("{}", val));
+    /// }
+    ///
+    /// executor.join();
+    /// ```
+    ///
+    /// # Note
+    ///
+    /// If you want to wait for the submitted tasks to finish executing, you should call `join` on the executor service.
+    pub fn execute<F: Send + 'static + FnOnce()>(&self, f: F) {
+        self.execute_boxed(Box::new(f))
+    }
+
+    /// Executes the already boxed closure as a `task` in a worker thread.
+    ///
+    /// Unlike `execute`, this is not generic over the closure type, so call sites that already hold boxed
+    /// closures don't instantiate a copy of the submission path per closure type, and the job is not boxed twice.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let (c1, c2) = (counter.clone(), counter.clone());
+    /// let name = String::from("boxed");
+    /// let jobs: Vec<Box<dyn FnOnce() + Send>> = vec![
+    ///     Box::new(move || {
+    ///         c1.fetch_add(1, Ordering::SeqCst);
+    ///     }),
+    ///     Box::new(move || {
+    ///         c2.fetch_add(name.len(), Ordering::SeqCst);
+    ///     }),
+    /// ];
+    ///
+    /// for job in jobs {
+    ///     executor.execute_boxed(job);
+    /// }
+    ///
+    /// executor.join();
+    /// assert_eq!(counter.load(Ordering::SeqCst), 6);
+    /// ```
+    pub fn execute_boxed(&self, job: Box<dyn FnOnce() + Send + 'static>) {
+        self.send_msg(Msg::Task(job))
+    }
+
+    /// Executes the given closure in a worker thread once `est_bytes` of the pool's memory budget are available.
+    ///
+    /// The worker that picks the task up blocks until enough of the budget set with `with_memory_budget` is
+    /// free, holds it while the task runs, and releases it afterwards, even if the task panics. A task
+    /// declaring more than the whole budget waits for all of it, and so runs alone among the sized tasks.
+    /// Without a memory budget, this behaves like `execute`.
+    pub fn execute_sized<F: Send + 'static + FnOnce()>(&self, est_bytes: usize, f: F) {
+        let Some(budget) = self.budget.clone() else {
+            return self.execute(f);
+        };
+        self.execute(move || {
+            let _permit = budget.acquire(est_bytes);
+            f()
+        });
+    }
+
+    /// Executes `f` on every item of the iterator in a worker thread, taking items lazily from the iterator.
+    ///
+    /// Items are taken from the iterator on the calling thread, which blocks while `prefetch` items (at least one)
+    /// have been taken but not processed yet, and returns once the iterator is exhausted and its last items are
+    /// submitted. This bounds the memory used by the queue when the source is huge or infinite.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let pulled = Arc::new(AtomicUsize::new(0));
+    /// let processed = Arc::new(AtomicUsize::new(0));
+    /// let max_ahead = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let (p, m) = (processed.clone(), max_ahead.clone());
+    /// let items = (0..100_000u64).inspect(|_| {
+    ///     let ahead = pulled.fetch_add(1, Ordering::SeqCst) + 1 - p.load(Ordering::SeqCst);
+    ///     m.fetch_max(ahead, Ordering::SeqCst);
+    /// });
+    /// let done = processed.clone();
+    /// executor.execute_stream(items, move |item| {
+    ///     std::hint::black_box(item);
+    ///     done.fetch_add(1, Ordering::SeqCst);
+    /// }, 100);
+    ///
+    /// executor.join();
+    /// assert_eq!(processed.load(Ordering::SeqCst), 100_000);
+    /// assert!(max_ahead.load(Ordering::SeqCst) <= 100);
+    /// ```
+    pub fn execute_stream<I, F>(&self, items: I, f: F, prefetch: usize)
+    where
+        I: Iterator,
+        I::Item: Send + 'static,
+        F: Fn(I::Item) + Send + Sync + 'static,
+    {
+        let window = Arc::new(Semaphore::new(prefetch.max(1)));
+        let f = Arc::new(f);
+        let mut items = items;
+        loop {
+            let permit = window.acquire(1);
+            let Some(item) = items.next() else {
+                break;
+            };
+            let f = f.clone();
+            self.execute(move || {
+                let _permit = permit;
+                f(item)
+            });
+        }
+    }
+
+    /// Runs `f` on every item in a worker thread, and concatenates the outputs each call produced.
+    ///
+    /// Every item is submitted as its own task, and its iterator is drained on the worker. Ordering across
+    /// items is preserved, so the outputs of `items[0]` come before those of `items[1]`, while the outputs
+    /// of a single item come in the order its iterator yields them. Blocks until every task has completed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the calls to `f`, or the iterator it returned, panicked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let output = executor.flat_map(vec![1, 2, 3], |n| 0..n);
+    /// assert_eq!(output, [0, 0, 1, 0, 1, 2]);
+    ///
+    /// // Items can produce no outputs at all
+    /// let evens = executor.flat_map(vec![1, 2, 3, 4], |n| (n % 2 == 0).then_some(n));
+    /// assert_eq!(evens, [2, 4]);
+    /// executor.join();
+    /// ```
+    pub fn flat_map<T, U, It, F>(&self, items: Vec<T>, f: F) -> Vec<U>
+    where
+        T: Send + 'static,
+        U: Send + 'static,
+        It: IntoIterator<Item = U>,
+        F: Fn(T) -> It + Send + Sync + Clone + 'static,
+    {
+        let handles: Vec<TaskHandle<Vec<U>>> = items
+            .into_iter()
+            .map(|item| {
+                let f = f.clone();
+                self.submit_handle(move || f(item).into_iter().collect())
+            })
+            .collect();
+        handles.into_iter().flat_map(TaskHandle::wait).collect()
+    }
+
+    /// Executes the given closure in a worker thread, queued under `label` for fair scheduling across labels.
+    ///
+    /// Each label has its own queue, and whenever a worker picks up a labeled task, it takes it from the next
+    /// label with queued tasks, round-robin. A burst of tasks under one label, such as one tenant, thus only
+    /// delays the tasks of other labels by one task per label, instead of by the whole burst. The workers are
+    /// still shared with the other tasks of the pool, which keep their place in the queue.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// let ran = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// // Keep the single worker busy until every task is queued
+    /// let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    /// executor.execute(move || release_rx.recv().unwrap());
+    ///
+    /// for i in 0..100 {
+    ///     let ran = ran.clone();
+    ///     executor.execute_labeled("bulk", move || ran.lock().unwrap().push(format!("bulk {i}")));
+    /// }
+    /// let interactive = ran.clone();
+    /// executor.execute_labeled("interactive", move || {
+    ///     interactive.lock().unwrap().push(String::from("interactive"))
+    /// });
+    /// release_tx.send(()).unwrap();
+    ///
+    /// executor.join();
+    /// let ran = ran.lock().unwrap();
+    /// assert_eq!(ran.len(), 101);
+    /// // The interactive task ran right after the first task of the burst, not after all of them
+    /// assert_eq!(ran[1], "interactive");
+    /// ```
+    pub fn execute_labeled<F: Send + 'static + FnOnce()>(&self, label: &str, f: F) {
+        if self.halted.load(Ordering::Acquire) {
+            return;
+        }
+        self.submitter
+            .send_labeled(label, Msg::Task(Box::new(f)))
+            .unwrap()
+    }
+
+    /// Executes the given closure in a worker thread, queued by `priority` and returning an id for `boost_task`.
+    ///
+    /// Whenever a worker picks up a prioritized task, it takes the queued one with the highest priority, and
+    /// tasks of equal priority run in submission order. Tasks executed by other methods keep their place in the
+    /// queue.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// let ran = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// // Keep the single worker busy until every task is queued
+    /// let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    /// executor.execute(move || release_rx.recv().unwrap());
+    ///
+    /// for (name, priority) in [("low", 1), ("high", 10), ("medium", 5)] {
+    ///     let ran = ran.clone();
+    ///     executor.execute_prioritized(priority, move || ran.lock().unwrap().push(name));
+    /// }
+    /// release_tx.send(()).unwrap();
+    ///
+    /// executor.join();
+    /// assert_eq!(*ran.lock().unwrap(), ["high", "medium", "low"]);
+    /// ```
+    pub fn execute_prioritized<F: Send + 'static + FnOnce()>(&self, priority: u32, f: F) -> TaskId {
+        let id = TaskId(self.named.next_id.fetch_add(1, Ordering::Relaxed));
+        if self.halted.load(Ordering::Acquire) {
+            return id;
+        }
+        self.submitter
+            .send_prioritized(id, priority, Msg::Task(Box::new(f)))
+            .unwrap();
+        id
+    }
+
+    /// Makes the tasks queued by `execute_prioritized` gain one level of priority for every `rate` they wait.
+    ///
+    /// Without aging, a steady stream of high-priority tasks keeps lower-priority ones queued forever. With it,
+    /// a worker picks the task with the highest effective priority, `priority + waited / rate`, so every task
+    /// eventually outranks the ones submitted after it, and runs. A task of priority `p` waits at most about
+    /// `(q - p) * rate` behind tasks of priority `q` submitted after it. Boosts raise the base priority.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let executor = ThreadPool::new(1).with_aging(Duration::from_millis(5));
+    /// let low_ran = Arc::new(AtomicBool::new(false));
+    ///
+    /// let ran = low_ran.clone();
+    /// executor.execute_prioritized(0, move || ran.store(true, Ordering::SeqCst));
+    ///
+    /// // Flood the pool with high-priority work, keeping tasks queued at all times
+    /// let start = Instant::now();
+    /// while !low_ran.load(Ordering::SeqCst) && start.elapsed() < Duration::from_secs(5) {
+    ///     if executor.pending() < 20 {
+    ///         executor.execute_prioritized(10, || std::thread::sleep(Duration::from_millis(1)));
+    ///     } else {
+    ///         std::thread::sleep(Duration::from_micros(100));
+    ///     }
+    /// }
+    ///
+    /// // The low-priority task ran while the flood went on, after about 10 * 5ms of waiting
+    /// assert!(low_ran.load(Ordering::SeqCst));
+    /// assert!(start.elapsed() < Duration::from_secs(1));
+    /// executor.join();
+    /// ```
+    pub fn with_aging(self, rate: Duration) -> Self {
+        assert!(!rate.is_zero(), "Aging rate must be non-zero");
+        self.submitter.priorities.set_aging(rate);
+        self
+    }
+
+    /// Raises the priority of a task executed with `execute_prioritized` that is still queued.
+    ///
+    /// This implements priority inheritance: when a high-priority task waits on a lock or a result held by a
+    /// queued low-priority task, boosting the latter to the priority of the former keeps tasks of intermediate
+    /// priority from delaying both. The queue is reordered right away, and the boost lasts until the task is
+    /// picked up by a worker. A priority lower than the current one is ignored, so boosts never demote a task.
+    ///
+    /// Returns whether the task was still queued, in which case it keeps its place among the tasks of its new
+    /// priority based on its submission order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// let ran = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    /// executor.execute(move || release_rx.recv().unwrap());
+    ///
+    /// let mut ids = Vec::new();
+    /// for i in 0..4 {
+    ///     let ran = ran.clone();
+    ///     ids.push(executor.execute_prioritized(1, move || ran.lock().unwrap().push(i)));
+    /// }
+    /// // A high-priority task is waiting on the last low-priority one
+    /// assert!(executor.boost_task(ids[3], 10));
+    /// release_tx.send(()).unwrap();
+    ///
+    /// executor.join();
+    /// assert_eq!(*ran.lock().unwrap(), [3, 0, 1, 2]);
+    /// ```
+    pub fn boost_task(&self, id: TaskId, new_priority: u32) -> bool {
+        self.submitter.priorities.boost(id, new_priority)
+    }
+
+    /// Executes a graph of tasks, running each task once all its prerequisites have completed.
+    ///
+    /// Tasks whose prerequisites have all completed are queued right away, so independent tasks run in
+    /// parallel. The graph is validated before any task is queued, and this returns without waiting for the
+    /// tasks; `join` waits for all of them. If a task panics, the tasks depending on it are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `DagError`, without running any task, if two nodes share an id, if a node lists an unknown
+    /// prerequisite, or if the prerequisites form a cycle.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::{DagError, DagNode, ThreadPool};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let order = Arc::new(Mutex::new(Vec::new()));
+    /// let running = Arc::new(AtomicUsize::new(0));
+    /// let max_running = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let node = |id: &'static str, prerequisites: &[&str]| {
+    ///     let (order, running, max_running) = (order.clone(), running.clone(), max_running.clone());
+    ///     DagNode::new(id, prerequisites.iter().copied(), move || {
+    ///         let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+    ///         max_running.fetch_max(now, Ordering::SeqCst);
+    ///         std::thread::sleep(Duration::from_millis(50));
+    ///         order.lock().unwrap().push(id);
+    ///         running.fetch_sub(1, Ordering::SeqCst);
+    ///     })
+    /// };
+    ///
+    /// // A diamond: B and C both need A, and D needs both of them
+    /// let diamond = vec![node("d", &["b", "c"]), node("b", &["a"]), node("c", &["a"]), node("a", &[])];
+    /// executor.schedule_dag(diamond).unwrap();
+    /// executor.join();
+    ///
+    /// let order = order.lock().unwrap();
+    /// assert_eq!(order.first(), Some(&"a"));
+    /// assert_eq!(order.last(), Some(&"d"));
+    /// // B and C ran at the same time
+    /// assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    ///
+    /// let executor = ThreadPool::new(2);
+    /// let cycle = vec![DagNode::new("x", ["y"], || ()), DagNode::new("y", ["x"], || ())];
+    /// assert!(matches!(executor.schedule_dag(cycle), Err(DagError::Cycle(_))));
+    /// executor.join();
+    /// ```
+    pub fn schedule_dag(&self, tasks: Vec<DagNode>) -> Result<(), DagError> {
+        let dag = Arc::new(Dag::new(tasks)?);
+        for (index, node) in dag.nodes.iter().enumerate() {
+            if node.remaining.load(Ordering::Acquire) == 0 {
+                Dag::submit(&dag, &self.submitter, index);
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps every item in a worker thread and folds the mapped values into `init` as they complete.
+    ///
+    /// Each mapped value is combined into the accumulator with `reduce`, under a lock, as soon as its task
+    /// completes, so the mapped values are never all held in memory at once. Blocks until every item has
+    /// been folded, and returns the accumulator.
+    ///
+    /// Values are folded in completion order, which varies from run to run. For the result to be
+    /// deterministic, folding must not depend on that order: `reduce` must be associative and commutative,
+    /// like a sum or a maximum.
+    ///
+    /// # Panics
+    ///
+    /// Panics once every item has been processed if a call to `map` panicked, with the payload of the first
+    /// such panic, or if a call to `reduce` panicked, since the accumulator is lost with it. Also panics if
+    /// the pool was joined or terminated, or its pending tasks aborted, before every item could be folded,
+    /// rather than returning a partial result.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let sum = executor.fold((0..1000u64).collect(), 0, |n| n * n, |sum, square| sum + square);
+    /// assert_eq!(sum, 332_833_500);
+    ///
+    /// // A panic in `map` is not silently left out of the result
+    /// let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+    ///     executor.fold(vec![1, 0, 2], 0, |n| 10 / n, |sum, quotient| sum + quotient)
+    /// }));
+    /// assert!(result.is_err());
+    ///
+    /// // Neither are items whose tasks were aborted before they could run
+    /// executor.pause();
+    /// let result = std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         std::thread::sleep(std::time::Duration::from_millis(50));
+    ///         executor.abort_pending();
+    ///         executor.resume();
+    ///     });
+    ///     std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+    ///         executor.fold((0..10).collect(), 0, |n| n, |sum, n| sum + n)
+    ///     }))
+    /// });
+    /// assert!(result.is_err());
+    /// executor.join();
+    /// ```
+    pub fn fold<T, P, A, F, G>(&self, items: Vec<T>, init: A, map: F, reduce: G) -> A
+    where
+        T: Send + 'static,
+        P: Send + 'static,
+        A: Send + 'static,
+        F: Fn(T) -> P + Send + Sync + 'static,
+        G: Fn(A, P) -> A + Send + Sync + 'static,
+    {
+        let expected = items.len();
+        let accumulator = Arc::new(std::sync::Mutex::new((Some(init), 0)));
+        let panicked = Arc::new(std::sync::Mutex::new(None));
+        let (map, reduce) = (Arc::new(map), Arc::new(reduce));
+        let batch = self.batch();
+        for item in items {
+            let (accumulator, panicked) = (accumulator.clone(), panicked.clone());
+            let (map, reduce) = (map.clone(), reduce.clone());
+            batch.execute(move || {
+                let partial = match panic::catch_unwind(AssertUnwindSafe(|| map(item))) {
+                    Ok(partial) => partial,
+                    Err(payload) => {
+                        let mut panicked = panicked.lock().unwrap_or_else(|e| e.into_inner());
+                        panicked.get_or_insert(payload);
+                        return;
+                    }
+                };
+                let mut accumulator = accumulator.lock().unwrap_or_else(|e| e.into_inner());
+                let (folded, count) = &mut *accumulator;
+                if let Some(acc) = folded.take() {
+                    *folded = Some(reduce(acc, partial));
+                    *count += 1;
+                }
+            });
+        }
+        batch.wait();
+        if let Some(payload) = panicked.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            panic::resume_unwind(payload);
+        }
+        let (folded, count) =
+            std::mem::take(&mut *accumulator.lock().unwrap_or_else(|e| e.into_inner()));
+        let folded = folded.expect("A call to reduce panicked");
+        assert!(
+            count == expected,
+            "The pool was stopped before every item was folded"
+        );
+        folded
+    }
+
+    /// Runs `f` on every chunk of the slice in parallel, splitting it into as many chunks as the pool has
+    /// workers, and blocks until every chunk has been processed.
+    ///
+    /// Handing out one task per chunk rather than per element keeps the scheduling overhead negligible for
+    /// numeric work on large slices. The chunks run as tasks on the workers of the pool, which this waits for
+    /// before returning, so that `f` can borrow the slice mutably without requiring `'static` data. Chunks
+    /// have the same length, except for the last one which may be shorter.
+    ///
+    /// Like `TaskGroup::wait`, this blocks while the pool is paused, and calling it from a task of the same
+    /// pool can deadlock once every worker is waiting on its own chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` panicked on any chunk, after every chunk has been processed, or if the pool was joined
+    /// or terminated before every chunk could run.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let mut data: Vec<u64> = (0..100_000).collect();
+    /// executor.process_chunks(&mut data, |chunk| chunk.iter_mut().for_each(|n| *n *= 2));
+    /// assert!(data.iter().enumerate().all(|(i, &n)| n == 2 * i as u64));
+    /// executor.join();
+    /// ```
+    pub fn process_chunks<T, F>(&self, data: &mut [T], f: F)
+    where
+        T: Send,
+        F: Fn(&mut [T]) + Sync,
+    {
+        if data.is_empty() {
+            return;
+        }
+        let chunk_len = data.len().div_ceil(self.size().max(1));
+        let f = &f;
+        self.run_scoped(
+            data.chunks_mut(chunk_len)
+                .map(|chunk| Box::new(move || f(chunk)) as ScopedJob<'_>)
+                .collect(),
+        );
+    }
+
+    /// Calls `f` on every index of `0..n` in parallel, returning the results in index order.
+    ///
+    /// Like `process_chunks`, the indices are split into as many contiguous ranges as the pool has workers,
+    /// each processed by a task on the workers of the pool, and this waits for all of them, so `f` can borrow
+    /// data from the caller without `Arc` or clones. Every result is written straight into its slot of the
+    /// returned `Vec`, which is allocated up front and split into one disjoint part per range, so no lock is
+    /// taken on the output. The same caveats as `process_chunks` apply to paused pools and nested calls.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` panicked on any index, after every range has been processed, or if the pool was joined
+    /// or terminated before every range could run. The results computed so far are leaked rather than dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let offsets = vec![1u64; 10_000];
+    /// let squares = executor.scope_indexed(10_000, |i| (i as u64) * (i as u64) + offsets[i]);
+    /// assert_eq!(squares.len(), 10_000);
+    /// assert!(squares.iter().enumerate().all(|(i, &n)| n == (i * i) as u64 + 1));
+    /// executor.join();
+    /// ```
+    pub fn scope_indexed<T, F>(&self, n: usize, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize) -> T + Sync,
+    {
+        let mut output = Vec::with_capacity(n);
+        if n == 0 {
+            return output;
+        }
+        let chunk_len = n.div_ceil(self.size().max(1));
+        let f = &f;
+        let slots = &mut output.spare_capacity_mut()[..n];
+        self.run_scoped(
+            slots
+                .chunks_mut(chunk_len)
+                .enumerate()
+                .map(|(chunk, slots)| {
+                    Box::new(move || {
+                        for (offset, slot) in slots.iter_mut().enumerate() {
+                            slot.write(f(chunk * chunk_len + offset));
+                        }
+                    }) as ScopedJob<'_>
+                })
+                .collect(),
+        );
+        // SAFETY: `run_scoped` only returns once every job has run to completion, and the jobs wrote every
+        // slot of `0..n` between them
+        unsafe { output.set_len(n) };
+        output
+    }
+
+    /// Runs every job as a task on the workers of the pool, and blocks until each one has run or been
+    /// discarded, which lets the jobs borrow from the stack of the caller.
+    ///
+    /// Panics with the payload of the first job that panicked, or if a job was discarded without running.
+    fn run_scoped(&self, jobs: Vec<ScopedJob<'_>>) {
+        let outcome = Arc::new(std::sync::Mutex::new(ScopeOutcome::default()));
+        let total = jobs.len();
+        // Waits for the submitted jobs even if submitting the next one panics
+        let tasks = WaitOnDrop(Arc::new(InFlight::default()));
+        for job in jobs {
+            // SAFETY: a `ScopedTask` drops its job before its guard, and `tasks` waits until every guard has
+            // been dropped before this function returns or unwinds, so no job outlives the borrows it holds
+            let job = unsafe { std::mem::transmute::<ScopedJob<'_>, ScopedJob<'static>>(job) };
+            let task = ScopedTask {
+                job,
+                outcome: outcome.clone(),
+                _guard: tasks.0.track(),
+            };
+            self.submitter.execute(move || task.run());
+        }
+        drop(tasks);
+        let outcome = std::mem::take(&mut *outcome.lock().unwrap_or_else(|e| e.into_inner()));
+        if let Some(payload) = outcome.panicked {
+            panic::resume_unwind(payload);
+        }
+        if outcome.completed < total {
+            panic!("The pool was stopped before every chunk could run");
+        }
+    }
+
+    /// Executes the given closure in a worker thread as a task with a human-readable name.
+    ///
+    /// While the task runs, it is listed with its name by `running_tasks`, which helps diagnosing a stuck
+    /// pool. The returned `TaskId` identifies the task in that list.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::new(2);
+    /// let (started_tx, started_rx) = std::sync::mpsc::channel();
+    /// let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    ///
+    /// let id = executor.execute_named("rebuild index", move || {
+    ///     started_tx.send(()).unwrap();
+    ///     release_rx.recv().unwrap();
+    /// });
+    ///
+    /// started_rx.recv().unwrap();
+    /// assert_eq!(executor.running_tasks(), [(id, String::from("rebuild index"))]);
+    ///
+    /// release_tx.send(()).unwrap();
+    /// executor.join();
+    /// ```
+    pub fn execute_named<F: Send + 'static + FnOnce()>(
+        &self,
+        name: impl Into<String>,
+        f: F,
+    ) -> TaskId {
+        let id = TaskId(self.named.next_id.fetch_add(1, Ordering::Relaxed));
+        let name = name.into();
+        let named = self.named.clone();
+        self.execute(move || {
+            named.running.lock().unwrap().insert(id, name);
+            let _running = RunningTask { named, id };
+            f()
+        });
+        id
+    }
+
+    /// Returns the id and name of every task executed with `execute_named` that is currently running.
+    ///
+    /// The tasks are listed in submission order.
+    pub fn running_tasks(&self) -> Vec<(TaskId, String)> {
+        let running = self.named.running.lock().unwrap();
+        running
+            .iter()
+            .map(|(id, name)| (*id, name.clone()))
+            .collect()
+    }
+
+    /// Executes the given closure in a worker thread and returns a `Receiver` for its result.
+    ///
+    /// The worker computes `f()` and sends the result down the channel, so the caller decides when,
+    /// and whether, to collect it with `recv` or `try_recv`. Dropping the receiver discards the result.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::new(3);
+    /// let receivers: Vec<_> = (0..5).map(|val| executor.submit(move || val * val)).collect();
+    ///
+    /// let results: Vec<i32> = receivers.iter().map(|rx| rx.recv().unwrap()).collect();
+    /// assert_eq!(results, vec![0, 1, 4, 9, 16]);
+    ///
+    /// executor.join();
+    /// ```
+    pub fn submit<T, F>(&self, f: F) -> std::sync::mpsc::Receiver<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.execute(move || {
+            // The caller may have dropped the receiver if it is not interested in the result
+            let _ = sender.send(f());
+        });
+        receiver
+    }
+
+    /// Executes the given closure in a worker thread and returns a `TaskHandle` for its result.
+    ///
+    /// Unlike the `Receiver` returned by `submit`, the handle can be consumed both from sync code, by
+    /// blocking on `wait`, and from async code, by awaiting it. See `TaskHandle` for details.
+    pub fn submit_handle<T, F>(&self, f: F) -> TaskHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let completion = Arc::new(Completion::default());
+        let completer = Completer(completion.clone());
+        self.execute(move || completer.complete(f()));
+        TaskHandle { completion }
+    }
+
+    /// Executes the given closure in a worker thread and passes its result to `c` on the same thread.
+    ///
+    /// Since the result never leaves the worker thread, it does not need to be `Send`, unlike with `submit`
+    /// or `execute_into`. This allows tasks producing thread-bound values such as `Rc`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::rc::Rc;
+    ///
+    /// let executor = ThreadPool::new(2);
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    ///
+    /// executor.execute_then(
+    ///     || Rc::new(vec![1, 2, 3]),
+    ///     move |shared| {
+    ///         let other = Rc::clone(&shared);
+    ///         tx.send(other.iter().sum::<i32>()).unwrap();
+    ///     },
+    /// );
+    ///
+    /// assert_eq!(rx.recv().unwrap(), 6);
+    /// executor.join();
+    /// ```
+    pub fn execute_then<T, F, C>(&self, f: F, c: C)
+    where
+        F: FnOnce() -> T + Send + 'static,
+        C: FnOnce(T) + Send + 'static,
+    {
+        self.execute(move || c(f()));
+    }
+
+    /// Executes the given closure in a worker thread and sends its result on the provided `Sender`.
+    ///
+    /// Passing clones of the same sender to several tasks fans all their results into one channel,
+    /// which a downstream consumer can read as a stream. If the receiver has been dropped, the result
+    /// is discarded without disturbing the worker.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    ///
+    /// for val in 0..10 {
+    ///     executor.execute_into(tx.clone(), move || val * 10);
+    /// }
+    /// drop(tx);
+    ///
+    /// let mut results: Vec<i32> = rx.iter().collect();
+    /// results.sort();
+    /// assert_eq!(results, (0..10).map(|val| val * 10).collect::<Vec<_>>());
+    ///
+    /// executor.join();
+    /// ```
+    pub fn execute_into<T, F>(&self, tx: std::sync::mpsc::Sender<T>, f: F)
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.execute(move || {
+            let _ = tx.send(f());
+        });
+    }
+
+    /// Executes the fallible closure in a worker thread, collecting the error it returns, if any.
+    ///
+    /// Errors of any type can be collected by the same pool, since they are boxed. They are kept until
+    /// drained with `take_errors`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::fmt;
+    ///
+    /// #[derive(Debug)]
+    /// struct Timeout(u32);
+    ///
+    /// impl fmt::Display for Timeout {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         write!(f, "timed out after {}s", self.0)
+    ///     }
+    /// }
+    ///
+    /// impl std::error::Error for Timeout {}
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// executor.execute_checked(|| Err(Box::new("x".parse::<i32>().unwrap_err())));
+    /// executor.execute_checked(|| Err(Box::new(std::io::Error::other("disk full"))));
+    /// executor.execute_checked(|| Ok(()));
+    /// executor.execute_checked(|| Err(Box::new(Timeout(30))));
+    ///
+    /// // A single worker runs the tasks in order, so the previous ones are done once this one is
+    /// executor.submit(|| ()).recv().unwrap();
+    ///
+    /// let errors: Vec<String> = executor.take_errors().iter().map(|e| e.to_string()).collect();
+    /// assert_eq!(errors, ["invalid digit found in string", "disk full", "timed out after 30s"]);
+    /// assert!(executor.take_errors().is_empty());
+    /// executor.join();
+    /// ```
+    pub fn execute_checked<F>(&self, f: F)
+    where
+        F: FnOnce() -> Result<(), Box<dyn std::error::Error + Send>> + Send + 'static,
+    {
+        let errors = self.errors.clone();
+        self.execute(move || {
+            if let Err(err) = f() {
+                errors.lock().unwrap().push(err);
+            }
+        });
+    }
+
+    /// Drains the errors collected from the tasks executed with `execute_checked`, in the order they were returned.
+    pub fn take_errors(&self) -> Vec<Box<dyn std::error::Error + Send>> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+
+    /// Executes the fallible closure in a worker thread, retrying it after `backoff` whenever it fails.
+    ///
+    /// `f` runs at most `max_attempts` times (and at least once), until it returns `Ok`. Between attempts,
+    /// the backoff is waited on the timer thread shared by the pools of the process rather than on the worker,
+    /// which stays free to run other tasks. `join` also waits for the pending retries. The error of the final attempt is discarded;
+    /// use `execute_retry_with` to handle it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let executor = ThreadPool::new(2);
+    /// let attempts = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let counter = attempts.clone();
+    /// executor.execute_retry(5, Duration::from_millis(10), move || {
+    ///     match counter.fetch_add(1, Ordering::SeqCst) {
+    ///         0 | 1 => Err("flaky"),
+    ///         _ => Ok(()),
+    ///     }
+    /// });
+    ///
+    /// executor.join();
+    /// assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    /// ```
+    pub fn execute_retry<E, F>(&self, max_attempts: usize, backoff: Duration, f: F)
+    where
+        E: 'static,
+        F: Fn() -> Result<(), E> + Send + 'static,
+    {
+        self.execute_retry_with(max_attempts, backoff, f, |_| {})
+    }
+
+    /// Like `execute_retry`, but passes the error of the final attempt to `on_give_up` if every attempt failed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// let executor = ThreadPool::new(2);
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    ///
+    /// executor.execute_retry_with(
+    ///     3,
+    ///     Duration::from_millis(1),
+    ///     || Err::<(), _>("down"),
+    ///     move |err| tx.send(err).unwrap(),
+    /// );
+    ///
+    /// assert_eq!(rx.recv().unwrap(), "down");
+    /// executor.join();
+    /// ```
+    pub fn execute_retry_with<E, F, G>(
+        &self,
+        max_attempts: usize,
+        backoff: Duration,
+        f: F,
+        on_give_up: G,
+    ) where
+        E: 'static,
+        F: Fn() -> Result<(), E> + Send + 'static,
+        G: FnOnce(E) + Send + 'static,
+    {
+        if self.halted.load(Ordering::Acquire) {
+            return;
+        }
+        let retry = Retry {
+            attempt: 1,
+            max_attempts,
+            backoff,
+            f,
+            on_give_up,
+        };
+        retry.submit(self.submitter.clone());
+    }
+
+    /// Executes the given closure in a worker thread, preferably on the worker with index `preferred`.
+    ///
+    /// If the preferred worker is idle, the task goes to its private inbox so that it runs there, close to
+    /// the data that worker touched last. Otherwise, or if there is no such worker, the task goes to the
+    /// shared queue like with `execute`, so it does not wait for the preferred worker to become free.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::mpsc::channel;
+    /// use std::time::Duration;
+    ///
+    /// let executor = ThreadPool::new(2);
+    /// executor.wait_for_idle_workers(2);
+    ///
+    /// // The idle preferred worker runs the task
+    /// let (done_tx, done_rx) = channel();
+    /// let done = done_tx.clone();
+    /// executor.execute_hinted(1, move || done.send("first").unwrap());
+    /// assert_eq!(done_rx.recv().unwrap(), "first");
+    /// executor.wait_for_idle_workers(2);
+    /// let tasks_run: Vec<usize> = executor.per_worker_stats().iter().map(|s| s.tasks_run).collect();
+    /// assert_eq!(tasks_run, [0, 1]);
+    ///
+    /// // A busy preferred worker does not hold the task back
+    /// let (started_tx, started_rx) = channel();
+    /// let (release_tx, release_rx) = channel::<()>();
+    /// executor.execute_hinted(1, move || {
+    ///     started_tx.send(()).unwrap();
+    ///     release_rx.recv().unwrap();
+    /// });
+    /// started_rx.recv().unwrap();
+    /// executor.execute_hinted(1, move || done_tx.send("second").unwrap());
+    /// assert_eq!(done_rx.recv_timeout(Duration::from_secs(1)), Ok("second"));
+    ///
+    /// release_tx.send(()).unwrap();
+    /// executor.join();
+    /// ```
+    pub fn execute_hinted<F: Send + 'static + FnOnce()>(&self, preferred: usize, f: F) {
+        if self.halted.load(Ordering::Acquire) {
+            return;
+        }
+        match self.workers.get(preferred) {
+            Some(worker) if IdleWorkers::claim(&worker.stats.idle) => {
+                let _ = self
+                    .submitter
+                    .send_msg_to(&worker.inbox, Msg::Task(Box::new(f)));
+            }
+            _ => self.send_msg(Msg::Task(Box::new(f))),
+        }
+    }
+
+    /// Executes the given closure in a worker thread, scheduled by its `deadline` (earliest deadline first).
+    ///
+    /// Among the tasks executed with this method, whenever a worker becomes free, it runs the one with the
+    /// nearest deadline, regardless of submission order. Tasks with equal deadlines run in submission order.
+    /// This only orders the tasks: a deadline that has passed does not cancel or skip the task, and there is
+    /// no guarantee that deadlines are met, e.g. when the pool is overloaded or tasks run longer than expected.
+    /// Tasks executed by other methods keep their place in the queue.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// let ran = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// // Keep the single worker busy until every task is queued
+    /// let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    /// executor.execute(move || release_rx.recv().unwrap());
+    ///
+    /// let now = Instant::now();
+    /// for i in (0..5).rev() {
+    ///     let ran = ran.clone();
+    ///     let deadline = now + Duration::from_millis(100 * i);
+    ///     executor.execute_by_deadline(deadline, move || ran.lock().unwrap().push(i));
+    /// }
+    /// release_tx.send(()).unwrap();
+    ///
+    /// executor.join();
+    /// assert_eq!(*ran.lock().unwrap(), [0, 1, 2, 3, 4]);
+    /// ```
+    pub fn execute_by_deadline<F: Send + 'static + FnOnce()>(
+        &self,
+        deadline: std::time::Instant,
+        f: F,
+    ) {
+        if self.halted.load(Ordering::Acquire) {
+            return;
+        }
+        self.submitter
+            .send_scheduled(deadline, Msg::Task(Box::new(f)))
+            .unwrap()
+    }
+
+    /// Executes the given closure in a worker thread, asking it to stop once it has run for `timeout`.
+    ///
+    /// A running closure cannot be preempted, so the timeout is cooperative: `f` receives a flag that is set
+    /// once `timeout` has elapsed since it started, and should check it periodically, e.g. in its loops, and
+    /// return early once it is set. A closure that keeps running for `timeout` after the flag was set is
+    /// logged as overrunning, as it likely ignores the flag. The timeout is kept by the timer thread shared by
+    /// the pools of the process, and is not enforced if that thread cannot be spawned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::Ordering;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// let (done_tx, done_rx) = std::sync::mpsc::channel();
+    ///
+    /// executor.execute_timed_coop(Duration::from_millis(50), move |timed_out| {
+    ///     let started = Instant::now();
+    ///     let mut steps = 0;
+    ///     // 10 seconds of work, unless interrupted
+    ///     while steps < 10_000 && !timed_out.load(Ordering::Acquire) {
+    ///         std::thread::sleep(Duration::from_millis(1));
+    ///         steps += 1;
+    ///     }
+    ///     done_tx.send((steps, started.elapsed())).unwrap();
+    /// });
+    ///
+    /// let (steps, elapsed) = done_rx.recv().unwrap();
+    /// assert!(steps < 10_000);
+    /// assert!(elapsed >= Duration::from_millis(50) && elapsed < Duration::from_secs(2));
+    /// executor.join();
+    /// ```
+    pub fn execute_timed_coop<F>(&self, timeout: Duration, f: F)
+    where
+        F: FnOnce(&AtomicBool) + Send + 'static,
+    {
+        self.execute(move || {
+            let started = std::time::Instant::now();
+            let timed_out = Arc::new(AtomicBool::new(false));
+            let finished = SetOnDrop(Arc::new(AtomicBool::new(false)));
+            let (flag, done) = (timed_out.clone(), finished.0.clone());
+            let expire: timer::Action = Box::new(move || {
+                if done.load(Ordering::Acquire) {
+                    return;
+                }
+                flag.store(true, Ordering::Release);
+                let overrun: timer::Action = Box::new(move || {
+                    if !done.load(Ordering::Acquire) {
+                        diag::warning!("task still running at twice its timeout of {timeout:?}, it may be ignoring its flag");
+                    }
+                });
+                let _ = timer::schedule(started + timeout * 2, overrun);
+            });
+            if timer::schedule(started + timeout, expire).is_err() {
+                diag::warning!("no timer thread available, running the task without its timeout of {timeout:?}");
+            }
+            f(&timed_out);
+        });
+    }
+
+    /// Executes a long task in slices, letting the other tasks of the pool interleave with it.
+    ///
+    /// `f` is called with a `Yielder`, which it should check periodically with `should_yield`. Once that
+    /// returns `true`, `f` should save its progress in its captured state and return: the worker then
+    /// re-enqueues it behind the waiting tasks and calls it again later. The task is complete once `f`
+    /// returns without having been asked to yield. This is cooperative, so a task that never checks
+    /// the yielder runs to completion in one go.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// let output = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// for id in ['a', 'b'] {
+    ///     let output = output.clone();
+    ///     let mut next = 0;
+    ///     executor.execute_yielding(move |yielder| {
+    ///         while next < 20 {
+    ///             std::thread::sleep(Duration::from_micros(500));
+    ///             output.lock().unwrap().push(id);
+    ///             next += 1;
+    ///             if yielder.should_yield() {
+    ///                 return;
+    ///             }
+    ///         }
+    ///     });
+    /// }
+    ///
+    /// executor.join();
+    /// let output = output.lock().unwrap();
+    /// assert_eq!(output.len(), 40);
+    /// // Both tasks shared the single worker instead of running one after the other
+    /// let switches = output.windows(2).filter(|pair| pair[0] != pair[1]).count();
+    /// assert!(switches > 1);
+    /// ```
+    pub fn execute_yielding<F: FnMut(&mut Yielder) + Send + 'static>(&self, f: F) {
+        if self.halted.load(Ordering::Acquire) {
+            return;
+        }
+        Yielder::submit(self.submitter.clone(), f);
+    }
+
+    /// Executes the given closure in a worker thread, or on the calling thread if the pool is saturated.
+    ///
+    /// If more than `high_water` tasks are already waiting to be picked up by a worker, `f` is run
+    /// synchronously on the current thread instead of being queued. This bounds the growth of the queue
+    /// and slows down submitters while the workers catch up, without ever blocking them indefinitely.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    /// let (started_tx, started_rx) = std::sync::mpsc::channel();
+    ///
+    /// // Occupy the only worker, then queue one more task behind it
+    /// executor.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     release_rx.recv().unwrap();
+    /// });
+    /// started_rx.recv().unwrap();
+    /// executor.execute(|| {});
+    ///
+    /// let (ran_on_tx, ran_on_rx) = std::sync::mpsc::channel();
+    /// executor.execute_caller_runs(0, move || {
+    ///     ran_on_tx.send(std::thread::current().id()).unwrap();
+    /// });
+    /// assert_eq!(ran_on_rx.recv().unwrap(), std::thread::current().id());
+    ///
+    /// release_tx.send(()).unwrap();
+    /// executor.join();
+    /// ```
+    pub fn execute_caller_runs<F: Send + 'static + FnOnce()>(&self, high_water: usize, f: F) {
+        if self.pending() > high_water {
+            f()
+        } else {
+            self.execute(f)
+        }
+    }
+
+    /// Executes the given future on a worker thread, multiplexed with the other futures owned by that worker.
+    ///
+    /// The future is picked up by whichever worker receives it first and pushed into that worker's
+    /// local set of futures. Rather than blocking on a single future, a worker polls all of its futures
+    /// cooperatively, so a pending future does not prevent the worker from making progress on the others
+    /// or from picking up new tasks.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use futures::channel::oneshot;
+    ///
+    /// let executor = ThreadPool::new(2);
+    /// let (results_tx, results_rx) = std::sync::mpsc::channel();
+    /// let mut inputs = Vec::new();
+    ///
+    /// for _ in 0..5 {
+    ///     let (tx, rx) = oneshot::channel::<i32>();
+    ///     let results_tx = results_tx.clone();
+    ///     inputs.push(tx);
+    ///     executor.execute_future_local(async move {
+    ///         let val = rx.await.unwrap();
+    ///         results_tx.send(val * 2).unwrap();
+    ///     });
+    /// }
+    ///
+    /// for (val, tx) in inputs.into_iter().enumerate() {
+    ///     tx.send(val as i32).unwrap();
+    /// }
+    ///
+    /// executor.join();
+    /// drop(results_tx);
+    ///
+    /// let mut results: Vec<i32> = results_rx.iter().collect();
+    /// results.sort();
+    /// assert_eq!(results, vec![0, 2, 4, 6, 8]);
+    /// ```
+    ///
+    /// # Note
+    ///
+    /// `join` waits for every future pushed this way to complete, so a future that never resolves will prevent `join` from returning.
+    pub fn execute_future_local<F: Future<Output = ()> + Send + 'static>(&self, f: F) {
+        self.send_msg(Msg::Future(Box::pin(f)))
+    }
+
+    /// Executes a steppable task in a worker thread, interleaving its steps with the other steppable tasks
+    /// and futures of that worker.
+    ///
+    /// The worker calls `step` round-robin on its pending steppable tasks, so one worker makes progress on
+    /// several blocking tasks instead of running each to completion before the next. Between rounds, the
+    /// worker also picks up new messages from the queue. See `SteppableTask` for details.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::{Step, SteppableTask, ThreadPool};
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// struct Download {
+    ///     id: char,
+    ///     chunks: usize,
+    ///     log: Arc<Mutex<Vec<char>>>,
+    /// }
+    ///
+    /// impl SteppableTask for Download {
+    ///     fn step(&mut self) -> Step {
+    ///         // Stands for a blocking read of the next chunk
+    ///         std::thread::sleep(Duration::from_millis(2));
+    ///         self.log.lock().unwrap().push(self.id);
+    ///         self.chunks -= 1;
+    ///         if self.chunks == 0 { Step::Done } else { Step::Pending }
+    ///     }
+    /// }
+    ///
+    /// let executor = ThreadPool::new(1);
+    /// let log = Arc::new(Mutex::new(Vec::new()));
+    /// for id in ['a', 'b', 'c'] {
+    ///     executor.execute_steppable(Download { id, chunks: 10, log: log.clone() });
+    /// }
+    ///
+    /// executor.join();
+    /// let log = log.lock().unwrap();
+    /// assert_eq!(log.len(), 30);
+    /// // The single worker interleaved the downloads instead of running them one after the other
+    /// let last_a = log.iter().rposition(|&id| id == 'a').unwrap();
+    /// let first_c = log.iter().position(|&id| id == 'c').unwrap();
+    /// assert!(first_c < last_a);
+    /// ```
+    pub fn execute_steppable(&self, task: impl SteppableTask + Send + 'static) {
+        self.execute_future_local(Stepping(task))
+    }
+
+    fn send_msg(&self, msg: Msg) {
+        if self.halted.load(Ordering::Acquire) {
+            return;
+        }
+        self.submitter.send_msg(msg).unwrap()
+    }
+
+    /// Stops the workers from starting queued tasks, until `resume` is called.
+    ///
+    /// Tasks that are already running finish normally, and tasks executed while the pool is paused are
+    /// queued as usual, without being lost. Since `join` waits for the queued tasks, it resumes the pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let executor = ThreadPool::new(4);
+    /// let group = executor.group();
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    ///
+    /// executor.pause();
+    /// for _ in 0..8 {
+    ///     let counter = counter.clone();
+    ///     group.execute(move || {
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///     });
+    /// }
+    ///
+    /// std::thread::sleep(Duration::from_millis(100));
+    /// assert_eq!(counter.load(Ordering::SeqCst), 0);
+    /// assert_eq!(executor.pending(), 8);
+    ///
+    /// executor.resume();
+    /// group.wait();
+    /// assert_eq!(counter.load(Ordering::SeqCst), 8);
+    /// executor.join();
+    /// ```
+    pub fn pause(&self) {
+        self.paused.set(true);
+    }
+
+    /// Lets the workers start queued tasks again after `pause`.
+    pub fn resume(&self) {
+        self.paused.set(false);
+    }
+
+    /// Returns `true` if the pool has been paused with `pause` and not resumed since.
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_paused()
+    }
+
+    /// Discards every task and future queued in the pool that no worker has picked up yet, returning how
+    /// many were discarded.
+    ///
+    /// Unlike `join_now` or `terminate`, this does not stop the pool: the workers keep running, the tasks
+    /// they are currently running complete, and tasks executed afterwards run as usual. This is meant for
+    /// test teardowns and emergency stops where the queued work is no longer wanted. Discarded tasks count
+    /// as completed for `join` and `TaskGroup::wait`. Tasks routed to a single worker by `execute_hinted`
+    /// wait in that worker's own queue and are not discarded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    /// let executor = ThreadPool::new(1);
+    ///
+    /// for _ in 0..100 {
+    ///     let counter = counter.clone();
+    ///     executor.execute(move || {
+    ///         std::thread::sleep(Duration::from_millis(10));
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///     });
+    /// }
+    /// let discarded = executor.abort_pending();
+    /// assert!(discarded >= 90);
+    ///
+    /// // The pool keeps accepting work
+    /// let c = counter.clone();
+    /// executor.execute(move || {
+    ///     c.fetch_add(1000, Ordering::SeqCst);
+    /// });
+    /// executor.join();
+    /// assert_eq!(counter.load(Ordering::SeqCst), 1000 + 100 - discarded);
+    /// ```
+    pub fn abort_pending(&self) -> usize {
+        self.discard_queued()
+    }
+
+    /// Discards the messages queued on the shared channel and behind tokens, returning how many tasks and
+    /// futures were discarded
+    fn discard_queued(&self) -> usize {
+        let mut discarded = 0;
+        // The workers hold the channel, so it is gone once they have all exited
+        if let Some(receiver) = self.receiver.upgrade() {
+            let mut terminations = 0;
+            while let Ok(msg) = receiver.try_recv() {
+                match self.submitter.resolve(msg) {
+                    Some(Msg::Terminate) => terminations += 1,
+                    // The message the token stood for has already been discarded
+                    None => {}
+                    // Dropping the message marks it as completed
+                    Some(_) => discarded += 1,
+                }
+            }
+            // Workers asked to exit still need to be told, and the queue was just drained to make room
+            for _ in 0..terminations {
+                let _ = self.submitter.sender.try_send(Msg::Terminate);
+            }
+        }
+        discarded += self.submitter.drain_queues();
+        self.submitter
+            .pending
+            .fetch_sub(discarded, Ordering::AcqRel);
+        discarded
+    }
+
+    /// Blocks the current thread until the `ThreadPool` completes all its executions
+    ///
+    /// Every task submitted before `join`, including tasks submitted by other tasks while the pool
+    /// drains, runs to completion before any worker is told to terminate. A paused pool is resumed first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    /// let executor = ThreadPool::new(8);
+    ///
+    /// for _ in 0..10_000 {
+    ///     let counter = counter.clone();
+    ///     executor.execute(move || {
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///     });
+    /// }
+    /// executor.join();
+    ///
+    /// assert_eq!(counter.load(Ordering::SeqCst), 10_000);
+    /// ```
+    pub fn join(self) {
+        self.resume();
+        self.submitter.in_flight.wait_idle();
+        // Submitters that outlive the pool now discard their tasks
+        self.halted.store(true, Ordering::Release);
+        let extra = self.autoscaler.map(Autoscaler::stop).unwrap_or_default();
+
+        for _ in 0..self.workers.len() + extra.len() {
+            // The channel is only disconnected once every worker has died, leaving no one to terminate
+            if self.submitter.sender.send(Msg::Terminate).is_err() {
+                break;
+            }
+        }
+
+        for Worker { thread, .. } in self.workers.into_iter().chain(extra) {
+            thread.join().unwrap();
+        }
+    }
+
+    /// Waits asynchronously until the `ThreadPool` completes all its executions.
+    ///
+    /// This is the asynchronous counterpart of `join`: the blocking join runs on tokio's blocking thread
+    /// pool, so awaiting it does not stall the runtime. The future must be driven by a tokio runtime,
+    /// such as the one of an `AsyncRuntime`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::{AsyncFlavor, AsyncRuntime, ThreadPool};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    /// let executor = ThreadPool::new(4);
+    ///
+    /// for _ in 0..100 {
+    ///     let counter = counter.clone();
+    ///     executor.execute(move || {
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///     });
+    /// }
+    ///
+    /// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+    /// runtime.poll(executor.join_async());
+    /// assert_eq!(counter.load(Ordering::SeqCst), 100);
+    /// ```
+    pub async fn join_async(self)
+    where
+        Q: Send + 'static,
+    {
+        if let Err(err) = tokio::task::spawn_blocking(move || self.join()).await {
+            if let Ok(payload) = err.try_into_panic() {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+
+    /// Stops the `ThreadPool` without draining its queue, blocking until every worker has exited.
+    ///
+    /// Tasks and futures that no worker has started yet are discarded. Each worker finishes the task it
+    /// is currently running, if any, then exits without pulling anything else from the queue. Futures
+    /// pushed with `execute_future_local` are dropped at their next await point.
+    ///
+    /// This is meant for emergency shutdowns where the pending work is no longer wanted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    /// let executor = ThreadPool::new(4);
+    ///
+    /// for _ in 0..100 {
+    ///     let counter = counter.clone();
+    ///     executor.execute(move || {
+    ///         std::thread::sleep(Duration::from_millis(10));
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///     });
+    /// }
+    /// executor.join_now();
+    ///
+    /// assert!(counter.load(Ordering::SeqCst) < 20);
+    /// ```
+    pub fn join_now(self) {
+        self.terminate();
+        let extra = self.autoscaler.map(Autoscaler::stop).unwrap_or_default();
+
+        for Worker { thread, .. } in self.workers.into_iter().chain(extra) {
+            thread.join().unwrap();
+        }
+    }
+
+    /// Stops the `ThreadPool` without draining its queue, and without waiting for the workers to exit.
+    ///
+    /// Each worker finishes the task it is currently running, if any, then exits without pulling anything
+    /// else from the queue. Tasks that no worker has started yet, whichever order the queue has, as well as
+    /// tasks executed after this call, are discarded. Use `join` or `join_now` afterwards to wait for the
+    /// workers to exit.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thread_runner::{Order, ThreadPool};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// type Task = Box<dyn FnOnce() + Send>;
+    ///
+    /// // Returns how many of 10 tasks completed, terminating the pool once the first one has started
+    /// fn completed(executor: ThreadPool, submit: impl Fn(&ThreadPool, Task)) -> usize {
+    ///     let counter = Arc::new(AtomicUsize::new(0));
+    ///     let (started_tx, started_rx) = std::sync::mpsc::channel();
+    ///
+    ///     for _ in 0..10 {
+    ///         let counter = counter.clone();
+    ///         let started_tx = started_tx.clone();
+    ///         submit(&executor, Box::new(move || {
+    ///             let _ = started_tx.send(());
+    ///             std::thread::sleep(Duration::from_millis(20));
+    ///             counter.fetch_add(1, Ordering::SeqCst);
+    ///         }));
+    ///     }
+    ///
+    ///     started_rx.recv().unwrap();
+    ///     executor.terminate();
+    ///     executor.execute(|| unreachable!());
+    ///     executor.join();
+    ///     counter.load(Ordering::SeqCst)
+    /// }
+    ///
+    /// // Only the task that was running when `terminate` was called completed
+    /// assert_eq!(completed(ThreadPool::new(1), |pool, task| pool.execute(task)), 1);
+    /// let lifo = ThreadPool::with_order(1, Order::Lifo);
+    /// assert_eq!(completed(lifo, |pool, task| pool.execute(task)), 1);
+    /// let by_deadline = |pool: &ThreadPool, task| pool.execute_by_deadline(Instant::now(), task);
+    /// assert_eq!(completed(ThreadPool::new(1), by_deadline), 1);
+    /// let labeled = |pool: &ThreadPool, task| pool.execute_labeled("tenant", task);
+    /// assert_eq!(completed(ThreadPool::new(1), labeled), 1);
+    /// let prioritized = |pool: &ThreadPool, task| {
+    ///     pool.execute_prioritized(1, task);
+    /// };
+    /// assert_eq!(completed(ThreadPool::new(1), prioritized), 1);
+    /// ```
+    pub fn terminate(&self) {
+        self.halted.store(true, Ordering::Release);
+        self.resume();
+        // Queued messages hold their tasks in flight, which would keep `join` waiting on them forever
+        self.discard_queued();
+
+        // Idle workers are blocked on the channel, so they still need a message to notice the halt
+        let extra = self
+            .autoscaler
+            .as_ref()
+            .map_or(0, Autoscaler::extra_workers);
+        for _ in 0..self.workers.len() + extra {
+            // A full queue already holds a message for every worker, and blocking here could wait forever
+            if self.submitter.sender.try_send(Msg::Terminate).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// A clonable handle for submitting tasks to a `ThreadPool`, obtained with `ThreadPool::submitter`.
+///
+/// A `Submitter` can be moved into the tasks running on the pool, so that a task can enqueue follow-up
+/// work from inside a worker thread, and `join` waits for such follow-up tasks as long as they are submitted
+/// before their parent task completes. Submitting to an unbounded pool never blocks. Submitting to a pool
+/// created with `ThreadPool::bounded` blocks while its queue is full, so tasks of a bounded pool that submit
+/// to it can deadlock once every worker is blocked that way.
+///
+/// Tasks submitted after the pool has been joined or terminated are discarded.
+///
+/// # Example
+///
+/// ```
+/// use thread_runner::ThreadPool;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::{Arc, Barrier};
+///
+/// let executor = ThreadPool::new(1);
+/// let submitter = executor.submitter();
+/// let counter = Arc::new(AtomicUsize::new(0));
+/// let barrier = Arc::new(Barrier::new(2));
+///
+/// let (c, b) = (counter.clone(), barrier.clone());
+/// executor.execute(move || {
+///     c.fetch_add(1, Ordering::SeqCst);
+///     let next = submitter.clone();
+///     submitter.execute(move || {
+///         c.fetch_add(1, Ordering::SeqCst);
+///         next.execute(move || {
+///             c.fetch_add(1, Ordering::SeqCst);
+///             b.wait();
+///         });
+///     });
+/// });
+///
+/// barrier.wait();
+/// assert_eq!(counter.load(Ordering::SeqCst), 3);
+///
+/// // Once the pool is joined, its tasks are discarded rather than run inline
+/// let late = executor.submitter();
+/// executor.join();
+/// late.execute(|| unreachable!());
+/// ```
+///
+/// # Note
+///
+/// Every task that submits another one grows the queue, so recursive self-submission without a base case
+/// grows the queue without bound and keeps `join` from ever returning.
+#[derive(Clone)]
+pub struct Submitter {
+    sender: Sender<Msg>,
+    pending: Arc<AtomicUsize>,
+    in_flight: Arc<InFlight>,
+    high_water: Arc<HighWaterHooks>,
+    latency: Option<Arc<LatencyTracker>>,
+    stack: Option<Arc<std::sync::Mutex<Vec<Msg>>>>,
+    live: Arc<AtomicUsize>,
+    halted: Arc<AtomicBool>,
+    deadlines: Arc<DeadlineQueue>,
+    labeled: Arc<LabeledQueues>,
+    priorities: Arc<PriorityQueue>,
+}
+
+impl Submitter {
+    /// Executes the given closure as a `task` in a worker thread of the pool.
+    pub fn execute<F: Send + 'static + FnOnce()>(&self, f: F) {
+        let _ = self.send_msg(Msg::Task(Box::new(f)));
+    }
+
+    /// Sends a task or future to the workers, counting it as pending until a worker picks it up
+    /// and as in flight until it has completed
+    fn send_msg(&self, msg: Msg) -> Result<(), crossbeam_channel::SendError<Msg>> {
+        let Some(msg) = self.admit(msg) else {
+            return Ok(());
+        };
+        // Counted before the message is queued, so that draining the queue never sees it uncounted
+        self.enqueue();
+        let msg = self.stacked(msg);
+        self.sender.send(msg).inspect_err(|_| {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+        })
+    }
+
+    /// Like `send_msg`, but queues the message by deadline and sends a token telling a worker to pop
+    /// the message with the earliest deadline
+    fn send_scheduled(
+        &self,
+        deadline: std::time::Instant,
+        msg: Msg,
+    ) -> Result<(), crossbeam_channel::SendError<Msg>> {
+        let Some(msg) = self.admit(msg) else {
+            return Ok(());
+        };
+        self.enqueue();
+        self.deadlines.push(deadline, msg);
+        self.sender.send(Msg::Scheduled).inspect_err(|_| {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+        })
+    }
+
+    /// Like `send_msg`, but queues the message under its label and sends a token telling a worker to pop
+    /// the message of the next label in turn
+    fn send_labeled(&self, label: &str, msg: Msg) -> Result<(), crossbeam_channel::SendError<Msg>> {
+        let Some(msg) = self.admit(msg) else {
+            return Ok(());
+        };
+        self.enqueue();
+        self.labeled.push(label, msg);
+        self.sender.send(Msg::Labeled).inspect_err(|_| {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+        })
+    }
+
+    /// Like `send_msg`, but queues the message by priority and sends a token telling a worker to pop
+    /// the message with the highest priority
+    fn send_prioritized(
+        &self,
+        id: TaskId,
+        priority: u32,
+        msg: Msg,
+    ) -> Result<(), crossbeam_channel::SendError<Msg>> {
+        let Some(msg) = self.admit(msg) else {
+            return Ok(());
+        };
+        self.enqueue();
+        self.priorities.push(id, priority, msg);
+        self.sender.send(Msg::Prioritized).inspect_err(|_| {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+        })
+    }
+
+    /// Like `send_msg`, but sends to the inbox of a single worker rather than to the shared channel
+    ///
+    /// The message is always sent, even to a halted pool, since the worker has been claimed to wait for it.
+    fn send_msg_to(
+        &self,
+        inbox: &Sender<Msg>,
+        msg: Msg,
+    ) -> Result<(), crossbeam_channel::SendError<Msg>> {
+        let msg = self.track(msg);
+        self.enqueue();
+        inbox.send(msg).inspect_err(|_| {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+        })
+    }
+
+    /// Like `send_msg`, but fails instead of blocking if the queue is full
+    fn try_send_msg(&self, msg: Msg) -> Result<(), crossbeam_channel::TrySendError<Msg>> {
+        let Some(msg) = self.admit(msg) else {
+            return Ok(());
+        };
+        self.enqueue();
+        self.sender.try_send(msg).inspect_err(|_| {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+        })
+    }
+
+    /// Tracks the message, or returns `None` if it has been dealt with without being queued
+    ///
+    /// Messages submitted once the pool has been terminated or joined are dropped. Messages submitted while
+    /// no live worker is left, which only happens when none could be spawned or they have all died for good,
+    /// are run inline, since nothing would ever pick them up.
+    fn admit(&self, msg: Msg) -> Option<Msg> {
+        if self.halted.load(Ordering::Acquire) {
+            return None;
+        }
+        let msg = self.track(msg);
+        if self.live.load(Ordering::Acquire) == 0 {
+            run_inline(msg);
+            return None;
+        }
+        Some(msg)
+    }
+
+    /// With LIFO ordering, pushes the message onto the stack and returns the token telling a worker to pop it
+    fn stacked(&self, msg: Msg) -> Msg {
+        match &self.stack {
+            Some(stack) => {
+                stack.lock().unwrap().push(msg);
+                Msg::Stacked
+            }
+            None => msg,
+        }
+    }
+
+    /// Takes every message out of the queues behind tokens, returning how many were discarded
+    ///
+    /// The tokens are left in the channel, and resolve to nothing once their queue is empty.
+    fn drain_queues(&self) -> usize {
+        let stacked = match &self.stack {
+            Some(stack) => std::mem::take(&mut *stack.lock().unwrap()),
+            None => Vec::new(),
+        };
+        let scheduled = self.deadlines.drain();
+        let labeled = self.labeled.drain();
+        let prioritized = self.priorities.drain();
+        // Dropping the messages marks them as completed
+        stacked.len() + scheduled.len() + labeled.len() + prioritized.len()
+    }
+
+    /// Replaces a token with the message it stands for, like `WorkerContext::resolve`
+    fn resolve(&self, msg: Msg) -> Option<Msg> {
+        resolve(
+            msg,
+            &self.stack,
+            &self.deadlines,
+            &self.labeled,
+            &self.priorities,
+        )
+    }
+
+    /// Counts one more pending task or future, notifying the high-water hooks it pushes the queue past
+    fn enqueue(&self) {
+        let depth = self.pending.fetch_add(1, Ordering::AcqRel) + 1;
+        self.high_water.crossed(depth);
+    }
+
+    /// Counts the task or future in flight until it has completed or been discarded
+    fn track(&self, msg: Msg) -> Msg {
+        let guard = self.in_flight.track();
+        match msg {
+            Msg::Task(job) => match self.latency.clone() {
+                Some(latency) => {
+                    let submitted = std::time::Instant::now();
+                    Msg::Task(Box::new(move || {
+                        let _guard = guard;
+                        let started = std::time::Instant::now();
+                        job();
+                        latency.record(started - submitted, started.elapsed());
+                    }))
+                }
+                None => Msg::Task(Box::new(move || {
+                    let _guard = guard;
+                    job()
+                })),
+            },
+            Msg::Future(fut) => Msg::Future(Box::pin(async move {
+                let _guard = guard;
+                fut.await
+            })),
+            msg @ (Msg::Terminate
+            | Msg::Stacked
+            | Msg::Scheduled
+            | Msg::Labeled
+            | Msg::Prioritized) => msg,
+        }
+    }
+}
+
+/// Identifies a task executed with `ThreadPool::execute_named` or `ThreadPool::execute_prioritized`, unique
+/// within its pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(u64);
+
+/// The tasks executed with `ThreadPool::execute_named` that are currently running, by id
+#[derive(Default)]
+struct NamedTasks {
+    next_id: std::sync::atomic::AtomicU64,
+    running: std::sync::Mutex<std::collections::BTreeMap<TaskId, String>>,
+}
+
+/// Removes a named task from the running tasks when dropped, whether it returned or panicked
+struct RunningTask {
+    named: Arc<NamedTasks>,
+    id: TaskId,
+}
+
+impl Drop for RunningTask {
+    fn drop(&mut self) {
+        let mut running = self.named.running.lock().unwrap_or_else(|e| e.into_inner());
+        running.remove(&self.id);
+    }
+}
+
+/// A counting semaphore, over the bytes of a pool's memory budget for `ThreadPool::with_memory_budget`, or over
+/// the items taken ahead of the workers for `ThreadPool::execute_stream`
+struct Semaphore {
+    max: usize,
+    used: std::sync::Mutex<usize>,
+    released: std::sync::Condvar,
+}
+
+impl Semaphore {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            used: std::sync::Mutex::new(0),
+            released: std::sync::Condvar::new(),
+        }
+    }
+
+    /// Blocks until `permits`, capped at the maximum, are available and takes them
+    fn acquire(self: &Arc<Self>, permits: usize) -> Permit {
+        let permits = permits.min(self.max);
+        let used = self.used.lock().unwrap();
+        let mut used = self
+            .released
+            .wait_while(used, |used| *used + permits > self.max)
+            .unwrap();
+        *used += permits;
+        Permit {
+            semaphore: self.clone(),
+            permits,
+        }
+    }
+}
+
+/// Gives the permits taken from a `Semaphore` back when dropped, whether the task returned or panicked
+struct Permit {
+    semaphore: Arc<Semaphore>,
+    permits: usize,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *self
+            .semaphore
+            .used
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) -= self.permits;
+        self.semaphore.released.notify_all();
+    }
+}
+
+/// Runs a task or future on the calling thread, for pools left without a live worker to run it
+fn run_inline(msg: Msg) {
+    match msg {
+        Msg::Task(job) => job(),
+        Msg::Future(fut) => futures::executor::block_on(fut),
+        Msg::Terminate | Msg::Stacked | Msg::Scheduled | Msg::Labeled | Msg::Prioritized => {}
+    }
+}
+
+/// A `ThreadPool` shared between clones, created with `ThreadPool::into_shared`.
+///
+/// Every clone dereferences to the same pool, so all of its methods taking `&self` can be called directly.
+/// The pool is joined, waiting for all of its tasks, when the last clone is dropped.
+///
+/// # Example
+///
+/// ```
+/// use thread_runner::ThreadPool;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// let shared = ThreadPool::new(4).into_shared();
+/// let counter = Arc::new(AtomicUsize::new(0));
+///
+/// let components: Vec<_> = (0..3)
+///     .map(|_| {
+///         let pool = shared.clone();
+///         let counter = counter.clone();
+///         std::thread::spawn(move || {
+///             assert_eq!(pool.size(), 4);
+///             for _ in 0..10 {
+///                 let counter = counter.clone();
+///                 pool.execute(move || {
+///                     counter.fetch_add(1, Ordering::SeqCst);
+///                 });
+///             }
+///         })
+///     })
+///     .collect();
+///
+/// for component in components {
+///     component.join().unwrap();
+/// }
+///
+/// // Dropping the last clone joins the pool
+/// drop(shared);
+/// assert_eq!(counter.load(Ordering::SeqCst), 30);
+/// ```
+///
+/// # Note
+///
+/// A clone should not be captured by the tasks of its own pool: if a task held the last one, dropping it would
+/// join the pool from one of its own workers, which never returns. Use `Submitter` to submit work from tasks.
+pub struct SharedThreadPool<Q = Unbounded> {
+    inner: Arc<SharedInner<Q>>,
+}
+
+/// Owns the pool of a `SharedThreadPool`, joining it when the last clone is dropped
+struct SharedInner<Q> {
+    pool: Option<ThreadPool<Q>>,
+}
+
+impl<Q> Drop for SharedInner<Q> {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.join();
+        }
+    }
+}
+
+impl<Q> Clone for SharedThreadPool<Q> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Q> std::ops::Deref for SharedThreadPool<Q> {
+    type Target = ThreadPool<Q>;
+
+    fn deref(&self) -> &Self::Target {
+        // The pool is only taken out when the last clone is dropped
+        self.inner.pool.as_ref().unwrap()
+    }
+}
+
+/// A group of tasks executed on a `ThreadPool`, created with `ThreadPool::group`.
+///
+/// Tasks executed through a group run on the shared pool like any other task, but the group keeps track of
+/// its own members, so `wait` only waits for them rather than for all the work of the pool. Several groups
+/// can coexist on one pool.
+///
+/// # Example
+///
+/// ```
+/// use thread_runner::ThreadPool;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let executor = ThreadPool::new(4);
+/// let slow = executor.group();
+/// let fast = executor.group();
+/// let slow_done = Arc::new(AtomicUsize::new(0));
+/// let fast_done = Arc::new(AtomicUsize::new(0));
+///
+/// for _ in 0..2 {
+///     let slow_done = slow_done.clone();
+///     slow.execute(move || {
+///         std::thread::sleep(Duration::from_millis(500));
+///         slow_done.fetch_add(1, Ordering::SeqCst);
+///     });
+/// }
+/// for _ in 0..4 {
+///     let fast_done = fast_done.clone();
+///     fast.execute(move || {
+///         fast_done.fetch_add(1, Ordering::SeqCst);
+///     });
+/// }
+///
+/// fast.wait();
+/// assert_eq!(fast_done.load(Ordering::SeqCst), 4);
+/// assert_eq!(slow_done.load(Ordering::SeqCst), 0);
+///
+/// slow.wait();
+/// assert_eq!(slow_done.load(Ordering::SeqCst), 2);
+/// executor.join();
+/// ```
+pub struct TaskGroup {
+    submitter: Submitter,
+    tasks: Arc<InFlight>,
+}
+
+impl TaskGroup {
+    /// Executes the given closure as a `task` of this group in a worker thread of the pool.
+    pub fn execute<F: Send + 'static + FnOnce()>(&self, f: F) {
+        let guard = self.tasks.track();
+        self.submitter.execute(move || {
+            let _guard = guard;
+            f()
+        });
+    }
+
+    /// Blocks the current thread until every task executed through this group has completed.
+    pub fn wait(&self) {
+        self.tasks.wait_idle();
+    }
+}
+
+/// A node of a tree of cancellable tasks, created with `ThreadPool::scope`.
+///
+/// `execute` submits a task under a new child scope, which it returns and also passes to the task, so the
+/// task can submit children of its own. Cancelling a scope cancels every scope below it: a task checks its
+/// scope and all its ancestors right before it starts, and is skipped if any of them is cancelled.
+///
+/// Like `CancelToken`, this is best-effort: a task that has already started runs to completion, unless it
+/// polls `is_cancelled` itself.
+///
+/// # Example
+///
+/// ```
+/// use thread_runner::ThreadPool;
+/// use std::sync::mpsc::channel;
+/// use std::sync::{Arc, Mutex};
+///
+/// let executor = ThreadPool::new(1);
+/// let ran = Arc::new(Mutex::new(Vec::new()));
+/// let (started, on_started) = channel();
+/// let (release, on_release) = channel::<()>();
+///
+/// let log = ran.clone();
+/// let parent = executor.scope().execute(move |scope| {
+///     for child in 0..3 {
+///         let log = log.clone();
+///         scope.execute(move |_| log.lock().unwrap().push(child));
+///     }
+///     started.send(()).unwrap();
+///     on_release.recv().unwrap();
+/// });
+///
+/// // The children are queued behind their parent on the single worker
+/// on_started.recv().unwrap();
+/// parent.cancel();
+/// release.send(()).unwrap();
+///
+/// executor.join();
+/// assert!(ran.lock().unwrap().is_empty());
+/// ```
+#[derive(Clone)]
+pub struct CancelScope {
+    submitter: Submitter,
+    /// The tokens of the root scope down to this one
+    tokens: Arc<[CancelToken]>,
+}
+
+impl CancelScope {
+    /// Executes the given closure as a `task` in a worker thread of the pool, under a new child scope.
+    ///
+    /// The task is skipped if this scope, or one of its ancestors, is cancelled by the time it starts.
+    /// Otherwise it is passed the returned child scope, under which it can execute tasks of its own.
+    pub fn execute<F>(&self, f: F) -> CancelScope
+    where
+        F: FnOnce(&CancelScope) + Send + 'static,
+    {
+        let child = self.child();
+        let scope = child.clone();
+        self.submitter.execute(move || {
+            if !scope.is_cancelled() {
+                f(&scope);
+            }
+        });
+        child
+    }
+
+    /// Creates a new scope below this one, cancelled along with it.
+    pub fn child(&self) -> CancelScope {
+        let tokens: Vec<CancelToken> = self
+            .tokens
+            .iter()
+            .cloned()
+            .chain(std::iter::once(CancelToken::new()))
+            .collect();
+        CancelScope {
+            submitter: self.submitter.clone(),
+            tokens: tokens.into(),
+        }
+    }
+
+    /// Cancels this scope and every scope below it, leaving its ancestors untouched.
+    pub fn cancel(&self) {
+        self.token().cancel();
+    }
+
+    /// Returns `true` if this scope or one of its ancestors has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.tokens.iter().any(CancelToken::is_cancelled)
+    }
+
+    /// Returns the token of this scope, which can be passed to the code expecting a `CancelToken`.
+    ///
+    /// The token only reflects the cancellation of this scope, not of its ancestors.
+    pub fn token(&self) -> &CancelToken {
+        self.tokens
+            .last()
+            .expect("a scope always has its own token")
+    }
+}
+
+/// A task of a graph executed with `ThreadPool::schedule_dag`, along with the ids of its prerequisites.
+pub struct DagNode {
+    id: String,
+    prerequisites: Vec<String>,
+    job: Job,
+}
+
+impl DagNode {
+    /// Creates a node running `f` once every node listed in `prerequisites` has completed.
+    pub fn new<F: Send + 'static + FnOnce()>(
+        id: impl Into<String>,
+        prerequisites: impl IntoIterator<Item = impl Into<String>>,
+        f: F,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            prerequisites: prerequisites.into_iter().map(Into::into).collect(),
+            job: Box::new(f),
+        }
+    }
+}
+
+/// The validated graph of a `ThreadPool::schedule_dag` call, shared by its running tasks
+struct Dag {
+    nodes: Vec<DagSlot>,
+}
+
+struct DagSlot {
+    job: std::sync::Mutex<Option<Job>>,
+    /// The number of prerequisites that have not completed yet
+    remaining: AtomicUsize,
+    /// The indices of the nodes listing this one as a prerequisite
+    dependents: Vec<usize>,
+}
+
+impl Dag {
+    fn new(tasks: Vec<DagNode>) -> Result<Self, DagError> {
+        let mut indices = std::collections::HashMap::with_capacity(tasks.len());
+        for (index, node) in tasks.iter().enumerate() {
+            if indices.insert(node.id.as_str(), index).is_some() {
+                return Err(DagError::DuplicateId(node.id.clone()));
+            }
+        }
+        let mut dependents = vec![Vec::new(); tasks.len()];
+        for (index, node) in tasks.iter().enumerate() {
+            for prerequisite in &node.prerequisites {
+                let Some(&before) = indices.get(prerequisite.as_str()) else {
+                    return Err(DagError::UnknownPrerequisite {
+                        id: node.id.clone(),
+                        prerequisite: prerequisite.clone(),
+                    });
+                };
+                dependents[before].push(index);
+            }
+        }
+
+        // Kahn's algorithm: the nodes never freed from their prerequisites are on a cycle or behind one
+        let mut remaining: Vec<usize> = tasks.iter().map(|node| node.prerequisites.len()).collect();
+        let mut ready: Vec<usize> = (0..tasks.len()).filter(|&i| remaining[i] == 0).collect();
+        let mut visited = 0;
+        while let Some(index) = ready.pop() {
+            visited += 1;
+            for &dependent in &dependents[index] {
+                remaining[dependent] -= 1;
+                if remaining[dependent] == 0 {
+                    ready.push(dependent);
+                }
+            }
+        }
+        if visited < tasks.len() {
+            let blocked = tasks
+                .iter()
+                .zip(&remaining)
+                .filter(|(_, &remaining)| remaining > 0)
+                .map(|(node, _)| node.id.clone())
+                .collect();
+            return Err(DagError::Cycle(blocked));
+        }
+
+        let nodes = tasks
+            .into_iter()
+            .zip(dependents)
+            .map(|(node, dependents)| DagSlot {
+                job: std::sync::Mutex::new(Some(node.job)),
+                remaining: AtomicUsize::new(node.prerequisites.len()),
+                dependents,
+            })
+            .collect();
+        Ok(Self { nodes })
+    }
+
+    /// Submits the node, which submits its dependents once it completes and they have no other prerequisite left
+    fn submit(dag: &Arc<Self>, submitter: &Submitter, index: usize) {
+        let (dag, next) = (dag.clone(), submitter.clone());
+        submitter.execute(move || {
+            let slot = &dag.nodes[index];
+            let job = slot.job.lock().unwrap().take();
+            if let Some(job) = job {
+                job();
+            }
+            for &dependent in &slot.dependents {
+                if dag.nodes[dependent]
+                    .remaining
+                    .fetch_sub(1, Ordering::AcqRel)
+                    == 1
+                {
+                    Dag::submit(&dag, &next, dependent);
+                }
+            }
+        });
+    }
+}
+
+/// The result of a task executed with `ThreadPool::submit_handle`, which can be waited on or awaited.
+///
+/// Sync code blocks on `wait`, while async code awaits the handle directly, since it implements `Future`.
+/// Dropping the handle discards the result, without cancelling the task.
+///
+/// When the result implements `Clone`, so does the handle, and every clone resolves to its own copy of the
+/// result, so several parts of a program can wait for the same task. Handles are `Unpin`, so they can be
+/// stored in a collection and raced with `futures::future::select_all`.
+///
+/// # Panics
+///
+/// Both `wait` and awaiting panic if the task panicked before producing its result.
+///
+/// # Example
+///
+/// ```
+/// use thread_runner::{AsyncFlavor, AsyncRuntime, ThreadPool};
+/// use std::time::Duration;
+///
+/// let executor = ThreadPool::new(4);
+///
+/// // Blocking from sync code
+/// let handle = executor.submit_handle(|| 6 * 7);
+/// assert_eq!(handle.wait(), 42);
+///
+/// // Awaiting from async code
+/// let runtime = AsyncRuntime::new(AsyncFlavor::CurrentThread);
+/// let handle = executor.submit_handle(|| String::from("done"));
+/// assert_eq!(runtime.poll(async { handle.await }), "done");
+///
+/// // Awaiting whichever task finishes first, while keeping a clone to observe another one
+/// let handles: Vec<_> = [300, 50, 200]
+///     .into_iter()
+///     .map(|ms| {
+///         executor.submit_handle(move || {
+///             std::thread::sleep(Duration::from_millis(ms));
+///             ms
+///         })
+///     })
+///     .collect();
+/// let slowest = handles[0].clone();
+/// let (first, index, _rest) = runtime.poll(futures::future::select_all(handles));
+/// assert_eq!((first, index), (50, 1));
+/// assert_eq!(slowest.wait(), 300);
+///
+/// executor.join();
+/// ```
+pub struct TaskHandle<T> {
+    completion: Arc<Completion<T>>,
+}
+
+impl<T> TaskHandle<T> {
+    /// Blocks the current thread until the task has completed, and returns its result.
+    pub fn wait(self) -> T {
+        futures::executor::block_on(self)
+    }
+}
+
+impl<T: Clone> Clone for TaskHandle<T> {
+    fn clone(&self) -> Self {
+        self.completion.state.lock().unwrap().cloner = Some(T::clone);
+        Self {
+            completion: self.completion.clone(),
+        }
+    }
+}
+
+impl<T> Future for TaskHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        // The completer may briefly outlive the end of the task, in which case the result is copied needlessly
+        let last = Arc::strong_count(&self.completion) == 1;
+        let mut state = self.completion.state.lock().unwrap();
+        if !state.finished {
+            if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                state.wakers.push(cx.waker().clone());
+            }
+            return Poll::Pending;
+        }
+        let result = match (state.cloner, last) {
+            (Some(clone), false) => state.result.as_ref().map(clone),
+            _ => state.result.take(),
+        };
+        Poll::Ready(result.expect("Task panicked before producing its result"))
+    }
+}
+
+/// The result of a task executed with `ThreadPool::submit_handle`, shared by the clones of its handle
+struct Completion<T> {
+    state: std::sync::Mutex<CompletionState<T>>,
+}
+
+impl<T> Default for Completion<T> {
+    fn default() -> Self {
+        Self {
+            state: std::sync::Mutex::new(CompletionState {
+                result: None,
+                finished: false,
+                cloner: None,
+                wakers: Vec::new(),
+            }),
+        }
+    }
+}
+
+struct CompletionState<T> {
+    result: Option<T>,
+    /// Set once the task has finished, whether it produced its result or panicked
+    finished: bool,
+    /// Copies the result for every handle but the last one, set once the handle has been cloned
+    cloner: Option<fn(&T) -> T>,
+    wakers: Vec<Waker>,
+}
+
+/// Stores the result of a task for its handles, and marks the task finished when dropped, even on a panic
+struct Completer<T>(Arc<Completion<T>>);
+
+impl<T> Completer<T> {
+    fn complete(self, result: T) {
+        self.0.state.lock().unwrap().result = Some(result);
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.finished = true;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// A batch of tasks executed on a `ThreadPool`, created with `ThreadPool::batch`.
+///
+/// Like a `TaskGroup`, a batch tracks only the tasks submitted through it, but it is consumed by `wait`,
+/// which makes it a single handle for a unit of work that is submitted and then awaited as a whole.
+///
+/// # Example
+///
+/// ```
+/// use thread_runner::ThreadPool;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let executor = ThreadPool::new(4);
+/// let quick = executor.batch();
+/// let slow = executor.batch();
+/// let quick_done = Arc::new(AtomicUsize::new(0));
+/// let slow_done = Arc::new(AtomicUsize::new(0));
+///
+/// for _ in 0..3 {
+///     let slow_done = slow_done.clone();
+///     slow.execute(move || {
+///         std::thread::sleep(Duration::from_millis(300));
+///         slow_done.fetch_add(1, Ordering::SeqCst);
+///     });
+///     let quick_done = quick_done.clone();
+///     quick.execute(move || {
+///         quick_done.fetch_add(1, Ordering::SeqCst);
+///     });
+/// }
+///
+/// quick.wait();
+/// assert_eq!(quick_done.load(Ordering::SeqCst), 3);
+/// assert_eq!(slow_done.load(Ordering::SeqCst), 0);
+///
+/// slow.wait();
+/// assert_eq!(slow_done.load(Ordering::SeqCst), 3);
+/// executor.join();
+/// ```
+pub struct Batch(TaskGroup);
+
+impl Batch {
+    /// Executes the given closure as a `task` of this batch in a worker thread of the pool.
+    pub fn execute<F: Send + 'static + FnOnce()>(&self, f: F) {
+        self.0.execute(f);
+    }
+
+    /// Blocks the current thread until every task executed through this batch has completed.
+    pub fn wait(self) {
+        self.0.wait();
+    }
+}
+
+/// Tells a task executed with `ThreadPool::execute_yielding` when to give its worker up to other tasks.
+pub struct Yielder {
+    started: std::time::Instant,
+    pending: Arc<AtomicUsize>,
+    yielded: bool,
+}
+
+impl Yielder {
+    /// Returns `true` if the task has used up its time slice while other tasks are waiting for a worker.
+    ///
+    /// Once this has returned `true`, the task should return as soon as possible; it will be called again
+    /// after the waiting tasks.
+    pub fn should_yield(&mut self) -> bool {
+        if !self.yielded && self.started.elapsed() >= YIELD_SLICE {
+            self.yielded = self.pending.load(Ordering::Acquire) > 0;
+        }
+        self.yielded
+    }
+
+    /// Submits the next slice of the task, which re-submits itself if it yields
+    fn submit<F: FnMut(&mut Yielder) + Send + 'static>(submitter: Submitter, mut f: F) {
+        let next = submitter.clone();
+        submitter.execute(move || {
+            let mut yielder = Yielder {
+                started: std::time::Instant::now(),
+                pending: next.pending.clone(),
+                yielded: false,
+            };
+            f(&mut yielder);
+            if yielder.yielded {
+                Yielder::submit(next, f);
+            }
+        });
+    }
+}
+
+/// A task that a worker runs one step at a time, submitted with `ThreadPool::execute_steppable`.
+///
+/// Each call to `step` should do a bounded amount of work, typically one blocking call, and report whether
+/// the task is complete. A task that is not done is stepped again once the worker has stepped its other
+/// tasks, so long steps delay all the tasks of the worker.
+pub trait SteppableTask {
+    /// Makes progress on the task, returning `Step::Done` once it is complete.
+    fn step(&mut self) -> Step;
+}
+
+/// The outcome of `SteppableTask::step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// The task has more work to do, and should be stepped again.
+    Pending,
+    /// The task is complete, and will not be stepped again.
+    Done,
+}
+
+thread_local! {
+    /// Raised when a steppable task of the current worker has taken a step and is not done yet
+    static STEPPED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Drives a steppable task as a future of a worker, taking one step per poll
+struct Stepping<T>(T);
+
+// The task is never pinned, only stepped through a plain mutable reference
+impl<T> Unpin for Stepping<T> {}
+
+impl<T: SteppableTask> Future for Stepping<T> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match self.0.step() {
+            Step::Done => Poll::Ready(()),
+            Step::Pending => {
+                // The task can always make progress, so it goes straight back to the end of the round
+                cx.waker().wake_by_ref();
+                STEPPED.with(|stepped| stepped.set(true));
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A task submitted with `ThreadPool::execute_retry_with`, along with its retry state
+struct Retry<F, G> {
+    attempt: usize,
+    max_attempts: usize,
+    backoff: Duration,
+    f: F,
+    on_give_up: G,
+}
+
+impl<E, F, G> Retry<F, G>
+where
+    E: 'static,
+    F: Fn() -> Result<(), E> + Send + 'static,
+    G: FnOnce(E) + Send + 'static,
+{
+    /// Submits the first attempt, which schedules the next one on the timer thread if it fails
+    fn submit(self, submitter: Submitter) {
+        let next = submitter.clone();
+        submitter.execute(move || self.attempt(next));
+    }
+
+    /// Runs the attempt on a worker, scheduling the next one on the timer thread if it fails
+    fn attempt(self, submitter: Submitter) {
+        let Err(err) = (self.f)() else {
+            return;
+        };
+        if self.attempt >= self.max_attempts {
+            return (self.on_give_up)(err);
+        }
+        // The retry stays in flight while waiting, so that `join` does not terminate the pool under it
+        let guard = submitter.in_flight.track();
+        let backoff = self.backoff;
+        let next = Retry {
+            attempt: self.attempt + 1,
+            ..self
+        };
+        Self::submit_after(
+            Arc::new(std::sync::Mutex::new(Some(next))),
+            submitter,
+            guard,
+            backoff,
+        );
+    }
+
+    /// Submits the retry once `delay` has passed.
+    ///
+    /// The timer thread serves every pool, so it must not block on a full queue: the retry stays in its
+    /// slot and is tried again after another `delay` until the queue has room.
+    fn submit_after(
+        next: Arc<std::sync::Mutex<Option<Self>>>,
+        submitter: Submitter,
+        guard: InFlightGuard,
+        delay: Duration,
+    ) {
+        let action: timer::Action = Box::new(move || {
+            let (retry, runner) = (next.clone(), submitter.clone());
+            let attempt = Msg::Task(Box::new(move || {
+                if let Some(retry) = retry.lock().unwrap().take() {
+                    retry.attempt(runner);
+                }
+            }));
+            match submitter.try_send_msg(attempt) {
+                Err(crossbeam_channel::TrySendError::Full(_)) => {
+                    Self::submit_after(next, submitter, guard, delay)
+                }
+                _ => drop(guard),
+            }
+        });
+        // Without a timer thread, the backoff is waited out where the retry is
+        if let Err(action) = timer::schedule(std::time::Instant::now() + delay, action) {
+            std::thread::sleep(delay);
+            action();
+        }
+    }
+}
+
+/// The callbacks registered with `ThreadPool::on_high_water`
+#[derive(Default)]
+struct HighWaterHooks {
+    hooks: std::sync::RwLock<Vec<Arc<HighWaterHook>>>,
+}
+
+struct HighWaterHook {
+    threshold: usize,
+    cb: Box<dyn Fn(usize) + Send + Sync>,
+}
+
+impl HighWaterHooks {
+    fn register(&self, threshold: usize, cb: impl Fn(usize) + Send + Sync + 'static) {
+        let hook = Arc::new(HighWaterHook {
+            threshold,
+            cb: Box::new(cb),
+        });
+        self.hooks.write().unwrap().push(hook);
+    }
+
+    /// Invokes the hooks whose threshold was crossed by the queue growing to `depth`
+    fn crossed(&self, depth: usize) {
+        let crossed: Vec<_> = self
+            .hooks
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|hook| hook.threshold + 1 == depth)
+            .cloned()
+            .collect();
+        // The lock is released first so that a callback may register further hooks
+        for hook in crossed {
+            (hook.cb)(depth);
+        }
+    }
+}
+
+/// The settings a pool is spawned with
+struct PoolConfig {
+    size: usize,
+    restart: Option<Arc<RestartPolicy>>,
+    stack_size: Option<usize>,
+    capacity: Option<usize>,
+    order: Order,
+    memory_budget: Option<usize>,
+    shared: bool,
+    runtime: Option<tokio::runtime::Handle>,
+    /// The maximum number of workers and the target queue depth of an autoscaling pool
+    autoscale: Option<(usize, usize)>,
+    #[cfg(feature = "rand")]
+    seed: Option<u64>,
+}
+
+impl PoolConfig {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            restart: None,
+            stack_size: None,
+            capacity: None,
+            order: Order::Fifo,
+            memory_budget: None,
+            shared: false,
+            runtime: None,
+            autoscale: None,
+            #[cfg(feature = "rand")]
+            seed: None,
+        }
+    }
+}
+
+/// The monitor of a pool created with `ThreadPool::autoscaling`, along with the workers it added
+struct Autoscaler {
+    /// Dropped to stop the monitor, which also happens when the pool is dropped without being joined
+    stop: std::sync::mpsc::Sender<()>,
+    monitor: std::thread::JoinHandle<()>,
+    extra: Arc<std::sync::Mutex<Vec<Worker>>>,
+}
+
+impl Autoscaler {
+    /// Starts the monitor, or returns `None` after logging the failure if its thread cannot be spawned
+    fn start(
+        context: WorkerContext,
+        min: usize,
+        max: usize,
+        target_queue_depth: usize,
+    ) -> Option<Self> {
+        let (stop, stopped) = std::sync::mpsc::channel();
+        // Holds at most one retirement, so that a slow retirement does not remove more workers
+        let (retire, retirements) = crossbeam_channel::bounded(1);
+        let extra = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let workers = extra.clone();
+        // Only the added workers can retire, so the pool never shrinks below its original workers
+        let extra_context = WorkerContext {
+            retire: Some(retirements.clone()),
+            ..context.clone()
+        };
+        // Ids are never reused, since retired workers may still be running when new ones are added
+        let mut next_id = min;
+        let monitor = std::thread::Builder::new().spawn(move || {
+            while let Err(std::sync::mpsc::RecvTimeoutError::Timeout) =
+                stopped.recv_timeout(AUTOSCALE_INTERVAL)
+            {
+                if context.halted.load(Ordering::Acquire) {
+                    break;
+                }
+                let live = context.live.load(Ordering::Acquire);
+                let depth = context.pending.load(Ordering::Acquire);
+                if depth > target_queue_depth && live < max {
+                    // The retirement asked for an emptier queue no longer applies
+                    let _ = retirements.try_recv();
+                    let mut workers = workers.lock().unwrap();
+                    // Workers that were retired have exited, and their threads can be released
+                    workers.retain(|worker: &Worker| !worker.thread.is_finished());
+                    for _ in 0..live.min(max - live) {
+                        workers.push(Worker::new(extra_context.clone(), next_id));
+                        next_id += 1;
+                    }
+                } else if depth == 0 && live > min {
+                    let _ = retire.try_send(());
+                }
+            }
+        });
+        match monitor {
+            Ok(monitor) => Some(Self {
+                stop,
+                monitor,
+                extra,
+            }),
+            Err(err) => {
+                diag::warning!(
+                    "failed to spawn autoscaling monitor ({err}), running without scaling"
+                );
+                None
+            }
+        }
+    }
+
+    /// Returns the number of workers added by the monitor that may still be running
+    fn extra_workers(&self) -> usize {
+        self.extra.lock().unwrap().len()
+    }
+
+    /// Stops the monitor, returning the workers it added
+    fn stop(self) -> Vec<Worker> {
+        drop(self.stop);
+        self.monitor.join().unwrap();
+        std::mem::take(&mut *self.extra.lock().unwrap())
+    }
+}
+
+/// The order in which the workers of a `ThreadPool` pick up queued tasks, set with `ThreadPool::with_order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// First in, first out: the task submitted first starts first.
+    Fifo,
+    /// Last in, first out: the task submitted last starts first.
+    Lifo,
+}
+
+/// How much work a single worker of a `ThreadPool` has done, returned by `ThreadPool::per_worker_stats`.
+#[derive(Debug, Clone)]
+pub struct WorkerStat {
+    /// The index of the worker within the pool.
+    pub worker_id: usize,
+    /// The number of tasks and futures the worker has completed.
+    pub tasks_run: usize,
+    /// The total time the worker has spent running tasks and polling futures.
+    pub time_busy: Duration,
+    /// The total time the worker has spent waiting for work, including the wait it may be in.
+    pub time_idle: Duration,
+}
+
+/// A worker thread.
+///
+/// A `Worker` runs a loop that listens for tasks on a channel, and executes
+/// each task as it arrives. It terminates when it receives a `Msg::Terminate`
+/// message.
+struct Worker {
+    thread: WorkerThread,
+    stats: Arc<WorkerStats>,
+    inbox: Sender<Msg>,
+}
+
+impl Worker {
+    /// New workers loop continuously in their own threads until they receive a Terminate message from the channel
+    ///
+    /// This terminate message is useful for joining the individual `JoinHandle<()>` objects during `join` of `ThreadPool`
+    ///
+    /// While the worker owns pending futures it blocks until either a message arrives or one of its futures is
+    /// woken, and alternates between driving its futures and taking new messages. After a terminate message, it only drives
+    /// its remaining futures to completion before exiting.
+    ///
+    /// If the pool is dropped without being joined, the channel hangs up once it is drained, which the workers
+    /// also treat as a terminate message.
+    fn new(context: WorkerContext, id: usize) -> Self {
+        let stats = Arc::new(WorkerStats::new(id));
+        let (inbox, receiver) = crossbeam_channel::unbounded();
+        Self {
+            thread: Self::spawn(context, stats.clone(), receiver),
+            stats,
+            inbox,
+        }
+    }
+
+    /// Spawns the thread running the worker loop.
+    ///
+    /// With a restart policy, a panic escaping the loop is caught and, if the policy allows it, a fresh
+    /// thread takes over the loop. The dying thread then waits on its replacement so that joining the
+    /// original handle still waits for the worker to finish.
+    ///
+    /// The thread is borrowed from the process-wide registry for the workers of shared pools.
+    ///
+    /// If the operating system fails to spawn the thread, the failure is logged and the worker is left out,
+    /// never counting as live. A pool left without any live worker runs its tasks inline.
+    fn spawn(
+        context: WorkerContext,
+        stats: Arc<WorkerStats>,
+        inbox: Receiver<Msg>,
+    ) -> WorkerThread {
+        // Counted before the thread starts, so that a new pool never looks like it has no live worker
+        context.live.fetch_add(1, Ordering::AcqRel);
+        let (shared, stack_size) = (context.shared, context.stack_size);
+        let (live, idle) = (context.live.clone(), context.idle.clone());
+        let spawned = WorkerThread::spawn(shared, stack_size, move || {
+            let live = LiveWorker(context.live.clone(), context.idle.clone());
+            let Some(policy) = context.restart.clone() else {
+                return Self::run(&context, &stats, &inbox);
+            };
+            let result =
+                panic::catch_unwind(AssertUnwindSafe(|| Self::run(&context, &stats, &inbox)));
+            let Err(payload) = result else {
+                return;
+            };
+            let reason = panic_message(payload.as_ref());
+            if policy.try_restart() {
+                diag::warning!("worker panicked ({reason}), restarting");
+                // The replacement is counted before this worker stops counting, so that the pool never
+                // looks like it has no live worker in between
+                let replacement = Self::spawn(context, stats, inbox);
+                drop(live);
+                if let Err(payload) = replacement.join() {
+                    panic::resume_unwind(payload);
+                }
+            } else {
+                diag::warning!("worker panicked ({reason}), restart limit reached");
+                policy.degraded.store(true, Ordering::Release);
+            }
+        });
+        spawned.unwrap_or_else(|err| {
+            diag::warning!("failed to spawn worker thread ({err}), running without it");
+            idle.exited(&live);
+            WorkerThread::Failed
+        })
+    }
+
+    fn run(context: &WorkerContext, stats: &WorkerStats, inbox: &Receiver<Msg>) {
+        let _runtime = context.runtime.as_ref().map(|handle| handle.enter());
+        #[cfg(feature = "rand")]
+        let _rng = context
+            .seed
+            .map(|seed| crate::execs::rng::seed(seed ^ stats.id as u64));
+        let mut local = LocalExecutor::new();
+        let mut terminating = false;
+        // A message taken from the shared channel while a hinted task was on its way to the inbox
+        let mut deferred = None;
+        loop {
+            if context.halted.load(Ordering::Acquire) {
+                break;
+            }
+            if !local.is_empty() {
+                let started = std::time::Instant::now();
+                let completed = local.run_until_stalled();
+                stats.record(completed, started.elapsed());
+            }
+            let msg = if terminating {
+                if !local.is_empty() {
+                    local.park(stats);
+                    continue;
+                }
+                // Messages sent to this worker alone would be lost with it
+                match inbox.try_recv() {
+                    Ok(msg) => msg,
+                    Err(_) => break,
+                }
+            } else if !local.is_empty() && context.paused.is_paused() {
+                local.park(stats);
+                continue;
+            } else {
+                stats.begin_idle();
+                let msg = Self::wait_for_msg(context, stats, inbox, &local, &mut deferred);
+                stats.end_idle();
+                match msg {
+                    Some(msg) => msg,
+                    None => continue,
+                }
+            };
+            // The pool may have been paused while this worker was already receiving
+            context.paused.wait();
+            if context.halted.load(Ordering::Acquire) {
+                context.discard(msg);
+                break;
+            }
+            let Some(msg) = context.resolve(msg) else {
+                continue;
+            };
+            match msg {
+                Msg::Terminate => terminating = true,
+                Msg::Task(job) => {
+                    context.pending.fetch_sub(1, Ordering::AcqRel);
+                    let started = std::time::Instant::now();
+                    job();
+                    stats.record(1, started.elapsed());
+                }
+                Msg::Future(fut) => {
+                    context.pending.fetch_sub(1, Ordering::AcqRel);
+                    local.push(fut)
+                }
+                Msg::Stacked | Msg::Scheduled | Msg::Labeled | Msg::Prioritized => {
+                    unreachable!("tokens are resolved by `resolve`")
+                }
+            }
+        }
+        // Only left behind when the pool has been halted
+        for msg in deferred.into_iter().chain(inbox.try_iter()) {
+            context.discard(msg);
+        }
+    }
+
+    /// Blocks until a message arrives on either the worker's inbox or the shared channel, or until one of
+    /// the worker's futures is woken, in which case this returns `None`.
+    ///
+    /// A worker without futures counts as idle while it waits, and `ThreadPool::execute_hinted` may claim it
+    /// for a task sent to its inbox. If the worker takes a message from the shared channel in the meantime, it
+    /// defers that message and waits for the claimed task, which would otherwise be stuck in the inbox.
+    fn wait_for_msg(
+        context: &WorkerContext,
+        stats: &WorkerStats,
+        inbox: &Receiver<Msg>,
+        local: &LocalExecutor,
+        deferred: &mut Option<Msg>,
+    ) -> Option<Msg> {
+        if let Some(msg) = deferred.take() {
+            return Some(msg);
+        }
+        if !local.is_empty() && local.is_woken() {
+            return inbox
+                .try_recv()
+                .or_else(|_| context.receiver.try_recv())
+                .ok();
+        }
+        let idle = local.is_empty().then(|| {
+            context.paused.wait();
+            context.idle.enter(&stats.idle)
+        });
+        let mut select = crossbeam_channel::Select::new();
+        let from_inbox = select.recv(inbox);
+        let from_shared = select.recv(&context.receiver);
+        let woken = (!local.is_empty()).then(|| select.recv(&local.wakeups));
+        let retired = match (&context.retire, &idle) {
+            (Some(retire), Some(_)) => Some(select.recv(retire)),
+            _ => None,
+        };
+        loop {
+            let op = select.select();
+            let index = op.index();
+            if index == from_inbox {
+                match op.recv(inbox) {
+                    Ok(msg) => return Some(msg),
+                    // The pool has been dropped, but the shared channel may still hold messages
+                    Err(_) => select.remove(index),
+                }
+            } else if index == from_shared {
+                // The pool and every submitter have been dropped, so nothing can be queued anymore
+                let msg = op.recv(&context.receiver).unwrap_or(Msg::Terminate);
+                if idle.as_ref().is_none_or(IdleGuard::leave) {
+                    return Some(msg);
+                }
+                return match inbox.recv() {
+                    Ok(hinted) => {
+                        *deferred = Some(msg);
+                        Some(hinted)
+                    }
+                    Err(_) => Some(msg),
+                };
+            } else if Some(index) == woken {
+                let _ = op.recv(&local.wakeups);
+                return None;
+            } else if let (Some(retire), true) = (&context.retire, Some(index) == retired) {
+                match op.recv(retire) {
+                    Ok(()) => return Some(Msg::Terminate),
+                    // The monitor has stopped, and the worker exits with the others
+                    Err(_) => select.remove(index),
+                }
+            }
+        }
+    }
+}
+
+/// The counters a worker updates as it runs tasks, read by `ThreadPool::per_worker_stats`
+struct WorkerStats {
+    id: usize,
+    tasks_run: AtomicUsize,
+    busy_nanos: std::sync::atomic::AtomicU64,
+    idle_nanos: std::sync::atomic::AtomicU64,
+    /// When the current idle period started, in nanoseconds after `epoch` plus one, or zero while busy
+    idle_since: std::sync::atomic::AtomicU64,
+    epoch: std::time::Instant,
+    /// Raised while the worker waits for a message, until `ThreadPool::execute_hinted` claims it, see `IdleWorkers`
+    idle: AtomicBool,
+}
+
+impl WorkerStats {
+    fn new(id: usize) -> Self {
+        Self {
+            id,
+            tasks_run: AtomicUsize::new(0),
+            busy_nanos: std::sync::atomic::AtomicU64::new(0),
+            idle_nanos: std::sync::atomic::AtomicU64::new(0),
+            idle_since: std::sync::atomic::AtomicU64::new(0),
+            epoch: std::time::Instant::now(),
+            idle: AtomicBool::new(false),
+        }
+    }
+
+    fn record(&self, tasks: usize, busy: Duration) {
+        self.tasks_run.fetch_add(tasks, Ordering::Relaxed);
+        self.busy_nanos
+            .fetch_add(busy.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Starts an idle period, which counts towards the idle time until `end_idle` is called
+    fn begin_idle(&self) {
+        let since = self.epoch.elapsed().as_nanos() as u64 + 1;
+        self.idle_since.store(since, Ordering::Relaxed);
+    }
+
+    fn end_idle(&self) {
+        let since = self.idle_since.swap(0, Ordering::Relaxed);
+        if since != 0 {
+            let idle = self.epoch.elapsed().as_nanos() as u64 - (since - 1);
+            self.idle_nanos.fetch_add(idle, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> WorkerStat {
+        let mut idle = self.idle_nanos.load(Ordering::Relaxed);
+        // A worker waiting for work has been idle since it started waiting, not only up to its last message
+        let since = self.idle_since.load(Ordering::Relaxed);
+        if since != 0 {
+            idle += (self.epoch.elapsed().as_nanos() as u64).saturating_sub(since - 1);
+        }
+        WorkerStat {
+            worker_id: self.id,
+            tasks_run: self.tasks_run.load(Ordering::Relaxed),
+            time_busy: Duration::from_nanos(self.busy_nanos.load(Ordering::Relaxed)),
+            time_idle: Duration::from_nanos(idle),
+        }
+    }
+}
+
+/// Counts the tasks and futures that have been submitted but have not completed yet
+#[derive(Default)]
+struct InFlight {
+    count: std::sync::Mutex<usize>,
+    idle: std::sync::Condvar,
+}
+
+impl InFlight {
+    /// Counts a new submission, which stays in flight until the returned guard is dropped
+    fn track(self: &Arc<Self>) -> InFlightGuard {
+        *self.count.lock().unwrap() += 1;
+        InFlightGuard(self.clone())
+    }
+
+    /// Blocks until every tracked submission has completed
+    fn wait_idle(&self) {
+        let count = self.count.lock().unwrap_or_else(|e| e.into_inner());
+        let idle = self.idle.wait_while(count, |count| *count > 0);
+        drop(idle.unwrap_or_else(|e| e.into_inner()));
+    }
+}
+
+/// A job borrowing from the stack of the caller of `ThreadPool::run_scoped`
+type ScopedJob<'scope> = Box<dyn FnOnce() + Send + 'scope>;
+
+/// A job submitted by `ThreadPool::run_scoped`, whose fields are dropped in order, so the job and its
+/// borrows are gone before the guard lets the caller return
+struct ScopedTask {
+    job: ScopedJob<'static>,
+    outcome: Arc<std::sync::Mutex<ScopeOutcome>>,
+    _guard: InFlightGuard,
+}
+
+impl ScopedTask {
+    fn run(self) {
+        let ScopedTask {
+            job,
+            outcome,
+            _guard,
+        } = self;
+        let result = panic::catch_unwind(AssertUnwindSafe(job));
+        let mut outcome = outcome.lock().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(()) => outcome.completed += 1,
+            Err(payload) => {
+                outcome.panicked.get_or_insert(payload);
+            }
+        }
+    }
+}
+
+/// How the jobs of `ThreadPool::run_scoped` went
+#[derive(Default)]
+struct ScopeOutcome {
+    completed: usize,
+    panicked: Option<Box<dyn std::any::Any + Send>>,
+}
+
+/// Blocks until every submission tracked by the inner `InFlight` has completed when dropped, even while
+/// unwinding
+struct WaitOnDrop(Arc<InFlight>);
+
+impl Drop for WaitOnDrop {
+    fn drop(&mut self) {
+        self.0.wait_idle();
+    }
+}
+
+/// Marks a submission as completed when dropped, whether it returned, panicked or was discarded
+struct InFlightGuard(Arc<InFlight>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut count = self.0.count.lock().unwrap_or_else(|e| e.into_inner());
+        *count -= 1;
+        if *count == 0 {
+            self.0.idle.notify_all();
+        }
+    }
+}
+
+/// The state shared by all the workers of a pool
+#[derive(Clone)]
+struct WorkerContext {
+    receiver: Arc<Receiver<Msg>>,
+    restart: Option<Arc<RestartPolicy>>,
+    pending: Arc<AtomicUsize>,
+    stack_size: Option<usize>,
+    shared: bool,
+    runtime: Option<tokio::runtime::Handle>,
+    halted: Arc<AtomicBool>,
+    paused: Arc<PauseGate>,
+    idle: Arc<IdleWorkers>,
+    stack: Option<Arc<std::sync::Mutex<Vec<Msg>>>>,
+    live: Arc<AtomicUsize>,
+    deadlines: Arc<DeadlineQueue>,
+    labeled: Arc<LabeledQueues>,
+    priorities: Arc<PriorityQueue>,
+    /// Receives the retirements asked by the monitor of an autoscaling pool, only for the workers it added
+    retire: Option<Receiver<()>>,
+    /// The base seed of the random number generators of the workers
+    #[cfg(feature = "rand")]
+    seed: Option<u64>,
+}
+
+/// Raises the flag when dropped, whether the code holding it returned or panicked
+struct SetOnDrop(Arc<AtomicBool>);
+
+impl Drop for SetOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// Stops counting a worker as live when dropped, whether its loop returned or panicked
+struct LiveWorker(Arc<AtomicUsize>, Arc<IdleWorkers>);
+
+impl Drop for LiveWorker {
+    fn drop(&mut self) {
+        self.1.exited(&self.0);
+    }
+}
+
+impl WorkerContext {
+    /// Drops a message the worker will not run, which then no longer counts as pending
+    ///
+    /// The message behind a token is discarded along with its queue by `ThreadPool::terminate`.
+    fn discard(&self, msg: Msg) {
+        if let Msg::Task(_) | Msg::Future(_) = msg {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Replaces a token with the message it stands for: the top of the stack of a LIFO pool for
+    /// `Msg::Stacked`, the message with the earliest deadline for `Msg::Scheduled`, the next message
+    /// of the round-robin across labels for `Msg::Labeled`, and the message with the highest priority for
+    /// `Msg::Prioritized`. Returns `None` if the message has been discarded since its token was sent.
+    fn resolve(&self, msg: Msg) -> Option<Msg> {
+        resolve(
+            msg,
+            &self.stack,
+            &self.deadlines,
+            &self.labeled,
+            &self.priorities,
+        )
+    }
+}
+
+/// Replaces a token with the message it stands for, taking it from the queue the token belongs to, or
+/// with nothing if the message has been discarded
+fn resolve(
+    msg: Msg,
+    stack: &Option<Arc<std::sync::Mutex<Vec<Msg>>>>,
+    deadlines: &DeadlineQueue,
+    labeled: &LabeledQueues,
+    priorities: &PriorityQueue,
+) -> Option<Msg> {
+    match (msg, stack) {
+        // The queues may have been drained by `ThreadPool::terminate` or `ThreadPool::abort_pending`
+        (Msg::Stacked, Some(stack)) => stack.lock().unwrap().pop(),
+        (Msg::Scheduled, _) => deadlines.pop(),
+        (Msg::Labeled, _) => labeled.pop(),
+        (Msg::Prioritized, _) => priorities.pop(),
+        (msg, _) => Some(msg),
+    }
+}
+
+/// The messages submitted with `ThreadPool::execute_by_deadline`, ordered earliest deadline first
+#[derive(Default)]
+struct DeadlineQueue {
+    heap: std::sync::Mutex<(std::collections::BinaryHeap<Scheduled>, u64)>,
+}
+
+impl DeadlineQueue {
+    fn push(&self, deadline: std::time::Instant, msg: Msg) {
+        let mut guard = self.heap.lock().unwrap();
+        let (heap, seq) = &mut *guard;
+        *seq += 1;
+        heap.push(Scheduled {
+            deadline,
+            seq: *seq,
+            msg,
+        });
+    }
+
+    fn pop(&self) -> Option<Msg> {
+        let mut guard = self.heap.lock().unwrap();
+        guard.0.pop().map(|scheduled| scheduled.msg)
+    }
+
+    /// Takes every queued message out, for `ThreadPool::terminate` and `ThreadPool::abort_pending`
+    fn drain(&self) -> Vec<Msg> {
+        let mut guard = self.heap.lock().unwrap();
+        guard.0.drain().map(|scheduled| scheduled.msg).collect()
+    }
+}
+
+/// The messages submitted with `ThreadPool::execute_labeled`, queued per label
+#[derive(Default)]
+struct LabeledQueues {
+    state: std::sync::Mutex<LabeledState>,
+}
+
+#[derive(Default)]
+struct LabeledState {
+    queues: std::collections::HashMap<String, std::collections::VecDeque<Msg>>,
+    /// The labels with queued messages, in the order they take their turn
+    turns: std::collections::VecDeque<String>,
+}
+
+impl LabeledQueues {
+    fn push(&self, label: &str, msg: Msg) {
+        let mut state = self.state.lock().unwrap();
+        let queue = state.queues.entry(label.to_string()).or_default();
+        queue.push_back(msg);
+        if queue.len() == 1 {
+            state.turns.push_back(label.to_string());
+        }
+    }
+
+    fn pop(&self) -> Option<Msg> {
+        let mut state = self.state.lock().unwrap();
+        let label = state.turns.pop_front()?;
+        let queue = state
+            .queues
+            .get_mut(&label)
+            .expect("A label in turn has a queue");
+        let msg = queue
+            .pop_front()
+            .expect("A label in turn has a queued message");
+        if queue.is_empty() {
+            state.queues.remove(&label);
+        } else {
+            state.turns.push_back(label);
+        }
+        Some(msg)
+    }
+
+    /// Takes every queued message out, for `ThreadPool::terminate` and `ThreadPool::abort_pending`
+    fn drain(&self) -> Vec<Msg> {
+        let state = std::mem::take(&mut *self.state.lock().unwrap());
+        state.queues.into_values().flatten().collect()
+    }
+}
+
+/// The messages submitted with `ThreadPool::execute_prioritized`, popped highest effective priority first
+struct PriorityQueue {
+    state: std::sync::Mutex<PriorityState>,
+    /// The start of the clock the submission times of the messages are measured on
+    epoch: std::time::Instant,
+}
+
+impl Default for PriorityQueue {
+    fn default() -> Self {
+        Self {
+            state: std::sync::Mutex::default(),
+            epoch: std::time::Instant::now(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PriorityState {
+    /// The queued messages by id, along with their current key in `order`
+    queued: std::collections::HashMap<TaskId, Prioritized>,
+    order: std::collections::BTreeSet<(PriorityKey, TaskId)>,
+    seq: u64,
+    /// How long a message waits to gain one level of priority, set with `ThreadPool::with_aging`
+    aging: Option<Duration>,
+}
+
+/// A message queued by priority
+struct Prioritized {
+    priority: u32,
+    /// When the message was queued, in nanoseconds since the epoch of the queue
+    queued_at: u128,
+    key: PriorityKey,
+    msg: Msg,
+}
+
+/// Orders the lowest rank first, then the earliest submission first
+type PriorityKey = (i128, u64);
+
+impl PriorityState {
+    /// Ranks a message so that the message with the highest effective priority comes first.
+    ///
+    /// With aging, the effective priority of a message is `priority + waited / rate`, which grows at the same
+    /// pace for every queued message. Their order thus never changes while they wait, and is the order of
+    /// `queued_at - priority * rate`, which can be computed once at submission instead of at every pop.
+    fn rank(&self, priority: u32, queued_at: u128) -> i128 {
+        match self.aging {
+            Some(rate) => queued_at as i128 - priority as i128 * rate.as_nanos() as i128,
+            None => -(priority as i128),
+        }
+    }
+
+    fn insert(&mut self, id: TaskId, mut prioritized: Prioritized) {
+        prioritized.key.0 = self.rank(prioritized.priority, prioritized.queued_at);
+        self.order.insert((prioritized.key, id));
+        self.queued.insert(id, prioritized);
+    }
+}
+
+impl PriorityQueue {
+    fn push(&self, id: TaskId, priority: u32, msg: Msg) {
+        let queued_at = self.epoch.elapsed().as_nanos();
+        let mut state = self.state.lock().unwrap();
+        state.seq += 1;
+        let prioritized = Prioritized {
+            priority,
+            queued_at,
+            key: (0, state.seq),
+            msg,
+        };
+        state.insert(id, prioritized);
+    }
+
+    fn pop(&self) -> Option<Msg> {
+        let mut state = self.state.lock().unwrap();
+        let (_, id) = state.order.pop_first()?;
+        Some(
+            state
+                .queued
+                .remove(&id)
+                .expect("An ordered id is queued")
+                .msg,
+        )
+    }
+
+    /// Takes every queued message out, for `ThreadPool::terminate` and `ThreadPool::abort_pending`
+    fn drain(&self) -> Vec<Msg> {
+        let mut state = self.state.lock().unwrap();
+        state.order.clear();
+        let queued = std::mem::take(&mut state.queued);
+        queued
+            .into_values()
+            .map(|prioritized| prioritized.msg)
+            .collect()
+    }
+
+    /// Raises the priority of a queued message, returning whether it was still queued
+    fn boost(&self, id: TaskId, priority: u32) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(mut prioritized) = state.queued.remove(&id) else {
+            return false;
+        };
+        if priority > prioritized.priority {
+            state.order.remove(&(prioritized.key, id));
+            prioritized.priority = priority;
+        }
+        state.insert(id, prioritized);
+        true
+    }
+
+    /// Makes the queued messages gain one level of priority for every `rate` they wait
+    fn set_aging(&self, rate: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.aging = Some(rate);
+        // The messages already queued are ranked again under the new rule
+        state.order.clear();
+        for (id, prioritized) in std::mem::take(&mut state.queued) {
+            state.insert(id, prioritized);
+        }
+    }
+}
+
+/// A message queued by deadline, with a sequence number keeping equal deadlines in submission order
+struct Scheduled {
+    deadline: std::time::Instant,
+    seq: u64,
+    msg: Msg,
+}
+
+impl Scheduled {
+    fn key(&self) -> std::cmp::Reverse<(std::time::Instant, u64)> {
+        std::cmp::Reverse((self.deadline, self.seq))
+    }
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    // `BinaryHeap` pops the greatest element, so the earliest deadline must compare as the greatest
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Holds the workers back from starting tasks while the pool is paused, set by `ThreadPool::pause`
+#[derive(Default)]
+struct PauseGate {
+    paused: AtomicBool,
+    lock: std::sync::Mutex<()>,
+    resumed: std::sync::Condvar,
+}
+
+impl PauseGate {
+    fn set(&self, paused: bool) {
+        // Flipping the flag under the lock keeps a worker from missing the wake-up between its check and its wait
+        let _lock = self.lock.lock().unwrap();
+        self.paused.store(paused, Ordering::Release);
+        if !paused {
+            self.resumed.notify_all();
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Blocks while the pool is paused
+    fn wait(&self) {
+        if !self.is_paused() {
+            return;
+        }
+        let lock = self.lock.lock().unwrap();
+        drop(self.resumed.wait_while(lock, |_| self.is_paused()).unwrap());
+    }
+}
+
+/// Counts the workers blocked waiting for a message, read by `ThreadPool::wait_for_idle_workers`
+///
+/// Each waiting worker also raises its own flag, which `ThreadPool::execute_hinted` lowers to claim that
+/// worker before sending a task to its inbox.
+#[derive(Default)]
+struct IdleWorkers {
+    count: std::sync::Mutex<usize>,
+    changed: std::sync::Condvar,
+}
+
+impl IdleWorkers {
+    /// Counts the current worker as idle until the returned guard is dropped, raising its flag meanwhile
+    fn enter<'a>(&'a self, flag: &'a AtomicBool) -> IdleGuard<'a> {
+        *self.count.lock().unwrap() += 1;
+        flag.store(true, Ordering::Release);
+        self.changed.notify_all();
+        IdleGuard {
+            workers: self,
+            flag,
+        }
+    }
+
+    /// Claims an idle worker by lowering its flag, returning `false` if it was not waiting or already claimed
+    fn claim(flag: &AtomicBool) -> bool {
+        flag.compare_exchange(true, false, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// Blocks until at least `n` workers are idle, or until fewer than `n` workers are live
+    fn wait_for(&self, n: usize, live: &AtomicUsize) {
+        let count = self.count.lock().unwrap();
+        let waiting = |count: &mut usize| *count < n && n <= live.load(Ordering::Acquire);
+        drop(self.changed.wait_while(count, waiting).unwrap());
+    }
+
+    /// Stops counting a worker as live, waking the waits it makes impossible
+    fn exited(&self, live: &AtomicUsize) {
+        // Taken so that a wait cannot miss the change between checking the count and blocking
+        let _count = self.count.lock().unwrap_or_else(|e| e.into_inner());
+        live.fetch_sub(1, Ordering::AcqRel);
+        self.changed.notify_all();
+    }
+}
+
+/// Marks a worker as busy again when dropped, once it has received a message
+struct IdleGuard<'a> {
+    workers: &'a IdleWorkers,
+    flag: &'a AtomicBool,
+}
+
+impl IdleGuard<'_> {
+    /// Lowers the flag of the worker, returning `false` if it had already been claimed
+    fn leave(&self) -> bool {
+        self.flag.swap(false, Ordering::AcqRel)
+    }
+}
+
+impl Drop for IdleGuard<'_> {
+    fn drop(&mut self) {
+        self.flag.store(false, Ordering::Release);
+        *self.workers.count.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+    }
+}
+
+/// Tracks how many times workers of a pool have been replaced after a panic
+struct RestartPolicy {
+    max_restarts: usize,
+    restarts: AtomicUsize,
+    degraded: AtomicBool,
+}
+
+impl RestartPolicy {
+    fn new(max_restarts: usize) -> Self {
+        Self {
+            max_restarts,
+            restarts: AtomicUsize::new(0),
+            degraded: AtomicBool::new(false),
+        }
+    }
+
+    /// Claims one restart, returning `false` if the pool has run out of restarts
+    fn try_restart(&self) -> bool {
+        self.restarts
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |restarts| {
+                (restarts < self.max_restarts).then_some(restarts + 1)
+            })
+            .is_ok()
+    }
+}
+
+/// The set of futures owned by a single worker, polled cooperatively on the worker's thread.
+struct LocalExecutor {
+    futures: FuturesUnordered<LocalJob>,
+    waker: Arc<WorkerWaker>,
+    /// Receives a wake-up whenever a future is woken, so that the worker can block on it alongside its channels
+    wakeups: Receiver<()>,
+}
+
+impl LocalExecutor {
+    fn new() -> Self {
+        let (wake, wakeups) = crossbeam_channel::bounded(1);
+        Self {
+            futures: FuturesUnordered::new(),
+            waker: Arc::new(WorkerWaker {
+                wake,
+                woken: AtomicBool::new(false),
+            }),
+            wakeups,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.futures.is_empty()
+    }
+
+    /// Returns whether futures have been woken since the last poll
+    fn is_woken(&self) -> bool {
+        self.waker.woken.load(Ordering::Acquire)
+    }
+
+    fn push(&mut self, fut: LocalJob) {
+        self.futures.push(fut);
+        self.waker.woken.store(true, Ordering::Release);
+    }
+
+    /// Polls the futures repeatedly until none of them has been woken since the last poll,
+    /// returning how many of them completed
+    ///
+    /// A steppable task is always ready, so this also stops after a round in which one was stepped, to let
+    /// the worker check the channel between steps. The futures woken meanwhile are polled on the next call.
+    fn run_until_stalled(&mut self) -> usize {
+        let waker = Waker::from(self.waker.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut completed = 0;
+        while self.waker.woken.swap(false, Ordering::AcqRel) {
+            STEPPED.with(|stepped| stepped.set(false));
+            while let Poll::Ready(Some(())) = self.futures.poll_next_unpin(&mut cx) {
+                completed += 1;
+            }
+            if STEPPED.with(|stepped| stepped.get()) {
+                break;
+            }
+        }
+        completed
+    }
+
+    /// Blocks until one of the futures is woken, without checking the channels
+    fn park(&self, stats: &WorkerStats) {
+        if !self.is_woken() {
+            stats.begin_idle();
+            let _ = self.wakeups.recv();
+            stats.end_idle();
+        }
+    }
+}
+
+/// Wakes a worker whose futures are ready to make progress
+struct WorkerWaker {
+    wake: Sender<()>,
+    woken: AtomicBool,
+}
+
+impl Wake for WorkerWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        // A wake-up already waiting is enough to get the worker going
+        let _ = self.wake.try_send(());
+    }
+}
+
+/// Represents a message that can be sent through the executor's channel.
+enum Msg {
+    /// Instructs the worker to terminate its execution.
+    Terminate,
+    /// Represents a task to be executed by the worker.
+    Task(Job),
+    /// Represents a future to be driven by the worker alongside its other futures.
+    Future(LocalJob),
+    /// Instructs the worker to pop the next message from the stack of a LIFO pool.
+    Stacked,
+    /// Instructs the worker to pop the message with the earliest deadline.
+    Scheduled,
+    /// Instructs the worker to pop the message of the next label with queued messages.
+    Labeled,
+    /// Instructs the worker to pop the message with the highest priority, which may have been raised with
+    /// `ThreadPool::boost_task` since this token was sent.
+    Prioritized,
+}
+
+/// A cheaply clonable, shared handle to `T` that can be moved across threads
 pub struct Redex<T> {
     data: std::sync::Arc<T>,
 }
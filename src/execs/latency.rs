@@ -0,0 +1,131 @@
+//! This module contains the latency histograms recorded by ThreadPool::with_latency_tracking
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The number of bits of each sample kept exactly, giving 2^3 = 8 buckets per power of two
+const SUB_BUCKET_BITS: u32 = 3;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = (64 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+/// The latency percentiles of the tasks of a `ThreadPool`, returned by `ThreadPool::latency_percentiles`.
+///
+/// Every duration is rounded up to the histogram bucket it falls in, which is within 12.5% of the
+/// recorded value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyReport {
+    /// The number of tasks recorded.
+    pub samples: u64,
+    /// The time tasks spent queued, from submission until a worker started running them.
+    pub queue: LatencyPercentiles,
+    /// The time tasks spent running, from start to finish.
+    pub run: LatencyPercentiles,
+}
+
+/// The 50th, 95th and 99th percentiles of a latency distribution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyPercentiles {
+    /// The median latency.
+    pub p50: Duration,
+    /// The latency that 95% of the tasks stayed under.
+    pub p95: Duration,
+    /// The latency that 99% of the tasks stayed under.
+    pub p99: Duration,
+}
+
+/// The queue and run latency histograms of a pool, updated by the workers as tasks complete
+#[derive(Default)]
+pub(crate) struct LatencyTracker {
+    queue: Histogram,
+    run: Histogram,
+}
+
+impl LatencyTracker {
+    pub(crate) fn record(&self, queue: Duration, run: Duration) {
+        self.queue.record(queue);
+        self.run.record(run);
+    }
+
+    pub(crate) fn report(&self) -> LatencyReport {
+        LatencyReport {
+            samples: self.run.samples(),
+            queue: self.queue.percentiles(),
+            run: self.run.percentiles(),
+        }
+    }
+}
+
+/// A lock-free histogram of durations with logarithmic buckets, each power of two being split
+/// into `SUB_BUCKETS` linear ones, in the style of HdrHistogram
+struct Histogram {
+    counts: Box<[AtomicU64]>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+}
+
+impl Histogram {
+    fn record(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.counts[bucket_of(nanos)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn samples(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    fn percentiles(&self) -> LatencyPercentiles {
+        let counts: Vec<u64> = self
+            .counts
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        let percentile = |quantile: f64| {
+            if total == 0 {
+                return Duration::ZERO;
+            }
+            let rank = ((quantile * total as f64).ceil() as u64).max(1);
+            let mut seen = 0;
+            for (bucket, count) in counts.iter().enumerate() {
+                seen += count;
+                if seen >= rank {
+                    return Duration::from_nanos(highest_in(bucket));
+                }
+            }
+            unreachable!("rank is at most the number of samples")
+        };
+        LatencyPercentiles {
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+        }
+    }
+}
+
+/// Returns the index of the bucket holding `nanos`
+fn bucket_of(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS as u64 {
+        return nanos as usize;
+    }
+    let magnitude = 63 - nanos.leading_zeros();
+    let shift = magnitude - SUB_BUCKET_BITS;
+    let sub_bucket = (nanos >> shift) as usize - SUB_BUCKETS;
+    (shift as usize + 1) * SUB_BUCKETS + sub_bucket
+}
+
+/// Returns the highest value that falls in the given bucket
+fn highest_in(bucket: usize) -> u64 {
+    if bucket < SUB_BUCKETS {
+        return bucket as u64;
+    }
+    let shift = bucket / SUB_BUCKETS - 1;
+    let sub_bucket = (bucket % SUB_BUCKETS + SUB_BUCKETS) as u128;
+    let next = (sub_bucket + 1) << shift;
+    u64::try_from(next - 1).unwrap_or(u64::MAX)
+}
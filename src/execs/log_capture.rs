@@ -0,0 +1,121 @@
+//! This module contains the logger behind AsyncRuntime::poll_capturing_logs
+
+use std::cell::RefCell;
+use std::sync::{Mutex, OnceLock};
+
+use crate::execs::diag;
+
+/// A record logged through the `log` crate, captured by `AsyncRuntime::poll_capturing_logs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedLog {
+    /// The level the record was logged at.
+    pub level: log::Level,
+    /// The target of the record, the module path of the call site unless set explicitly.
+    pub target: String,
+    /// The formatted message of the record.
+    pub message: String,
+}
+
+thread_local! {
+    /// The records captured on this thread, while a capture is active
+    static CAPTURED: RefCell<Option<Vec<CapturedLog>>> = const { RefCell::new(None) };
+}
+
+/// The global logger, which stores the records of the threads that are capturing and drops the others
+struct CapturingLogger;
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+        CAPTURED.with(|captured| captured.borrow().is_some())
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        CAPTURED.with(|captured| {
+            if let Some(captured) = captured.borrow_mut().as_mut() {
+                captured.push(CapturedLog {
+                    level: record.level(),
+                    target: record.target().to_string(),
+                    message: record.args().to_string(),
+                });
+            }
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger;
+
+/// Whether the capturing logger is the global logger, decided on the first capture
+static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+/// The number of threads capturing, and the max level to restore once none is
+static ACTIVE: Mutex<(usize, log::LevelFilter)> = Mutex::new((0, log::LevelFilter::Off));
+
+/// Runs `f` while capturing the records logged on the current thread.
+///
+/// This is intended for test binaries: the capturing logger is installed as the global logger on first
+/// use, but only if no logger was installed before, in which case a message is logged and nothing is
+/// captured. The max level is raised to `Trace` while a thread is capturing, and restored afterwards.
+pub(crate) fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<CapturedLog>) {
+    let installed = *INSTALLED.get_or_init(|| match log::set_logger(&LOGGER) {
+        Ok(()) => true,
+        Err(_) => {
+            diag::warning!("a logger is already installed, logs will not be captured");
+            false
+        }
+    });
+    let capturing = Capturing::start(installed);
+    let output = f();
+    let logs = capturing.finish();
+    (output, logs)
+}
+
+/// A capture in progress on the current thread, which is ended when dropped, even if `f` panicked
+struct Capturing {
+    /// The records of the capture this one is nested in, restored once it ends
+    outer: Option<Option<Vec<CapturedLog>>>,
+    installed: bool,
+}
+
+impl Capturing {
+    fn start(installed: bool) -> Self {
+        if installed {
+            let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+            if active.0 == 0 {
+                active.1 = log::max_level();
+                log::set_max_level(log::LevelFilter::Trace);
+            }
+            active.0 += 1;
+        }
+        let outer = CAPTURED.with(|captured| captured.borrow_mut().replace(Vec::new()));
+        Self {
+            outer: Some(outer),
+            installed,
+        }
+    }
+
+    /// Ends the capture, returning the records it captured
+    fn finish(mut self) -> Vec<CapturedLog> {
+        self.end().unwrap_or_default()
+    }
+
+    fn end(&mut self) -> Option<Vec<CapturedLog>> {
+        let outer = self.outer.take()?;
+        let logs = CAPTURED.with(|captured| std::mem::replace(&mut *captured.borrow_mut(), outer));
+        if self.installed {
+            let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+            active.0 -= 1;
+            if active.0 == 0 {
+                log::set_max_level(active.1);
+            }
+        }
+        logs
+    }
+}
+
+impl Drop for Capturing {
+    fn drop(&mut self) {
+        self.end();
+    }
+}
@@ -1,5 +1,28 @@
 pub mod executor;
 pub mod async_rt;
+pub mod task_queue;
+pub mod error;
+pub mod cancel;
+pub mod condvar;
+mod diag;
+mod env;
+mod latency;
+#[cfg(feature = "log")]
+mod log_capture;
+mod threads;
+mod timer;
+#[cfg(feature = "rand")]
+mod rng;
 
 pub use executor::*;
-pub use async_rt::*;
\ No newline at end of file
+pub use async_rt::*;
+pub use task_queue::*;
+pub use error::*;
+pub use cancel::*;
+pub use condvar::*;
+pub use env::WORKERS_ENV_VAR;
+pub use latency::{LatencyPercentiles, LatencyReport};
+#[cfg(feature = "log")]
+pub use log_capture::CapturedLog;
+#[cfg(feature = "rand")]
+pub use rng::with_rng;
\ No newline at end of file
@@ -0,0 +1,57 @@
+//! This module contains the per-worker random number generators of ThreadPool::with_seeded_rng
+
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use std::cell::RefCell;
+
+thread_local! {
+    /// The generator of the worker running on this thread, while it belongs to a seeded pool
+    static RNG: RefCell<Option<SmallRng>> = const { RefCell::new(None) };
+}
+
+/// Calls `f` with the random number generator of the current worker of a pool created with
+/// `ThreadPool::with_seeded_rng`.
+///
+/// Only available with the `rand` feature.
+///
+/// # Panics
+///
+/// Panics if the current thread is not a worker of a seeded pool, or if called from within `f`.
+///
+/// # Example
+///
+/// ```
+/// use rand::Rng;
+/// use thread_runner::{with_rng, ThreadPool};
+///
+/// let executor = ThreadPool::with_seeded_rng(2, 7);
+/// let roll = executor.submit(|| with_rng(|rng| rng.gen_range(1..=6)));
+/// assert!((1..=6).contains(&roll.recv().unwrap()));
+/// executor.join();
+/// ```
+pub fn with_rng<T>(f: impl FnOnce(&mut SmallRng) -> T) -> T {
+    RNG.with(|rng| {
+        let mut rng = rng.borrow_mut();
+        let rng = rng
+            .as_mut()
+            .expect("with_rng called outside a worker of a seeded pool");
+        f(rng)
+    })
+}
+
+/// Seeds the generator of the current thread, until the returned guard is dropped
+pub(crate) fn seed(seed: u64) -> SeededRng {
+    RNG.with(|rng| *rng.borrow_mut() = Some(SmallRng::seed_from_u64(seed)));
+    SeededRng
+}
+
+/// Removes the generator of the current thread when dropped, so that a registry thread does not keep it
+/// for its next pool
+pub(crate) struct SeededRng;
+
+impl Drop for SeededRng {
+    fn drop(&mut self) {
+        // The thread-local may already be destroyed if the thread is exiting
+        let _ = RNG.try_with(|rng| rng.borrow_mut().take());
+    }
+}
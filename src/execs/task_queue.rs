@@ -0,0 +1,56 @@
+//! This module contains the TaskQueue trait shared by the pools of this crate
+
+use crate::ThreadPool;
+
+/// A queue of tasks executed concurrently, abstracting over the pool types of this crate.
+///
+/// This allows writing utilities that work with any pool. The concrete pools keep their
+/// generic inherent methods, which should be preferred when the pool type is known.
+///
+/// # Example
+///
+/// ```
+/// use thread_runner::{TaskQueue, ThreadPool};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// fn run_all(queue: &impl TaskQueue, counter: &Arc<AtomicUsize>, jobs: usize) {
+///     for _ in 0..jobs {
+///         let counter = counter.clone();
+///         queue.execute(Box::new(move || {
+///             counter.fetch_add(1, Ordering::SeqCst);
+///         }));
+///     }
+/// }
+///
+/// let counter = Arc::new(AtomicUsize::new(0));
+///
+/// let executor = ThreadPool::new(4);
+/// run_all(&executor, &counter, 10);
+/// TaskQueue::join(executor);
+///
+/// let executor = ThreadPool::with_restart_on_panic(2, 1);
+/// run_all(&executor, &counter, 10);
+/// TaskQueue::join(executor);
+///
+/// assert_eq!(counter.load(Ordering::SeqCst), 20);
+/// ```
+pub trait TaskQueue {
+    /// Submits the boxed closure to be executed as a task.
+    fn execute(&self, f: Box<dyn FnOnce() + Send>);
+
+    /// Blocks the current thread until all the submitted tasks have completed.
+    fn join(self)
+    where
+        Self: Sized;
+}
+
+impl<Q> TaskQueue for ThreadPool<Q> {
+    fn execute(&self, f: Box<dyn FnOnce() + Send>) {
+        ThreadPool::execute_boxed(self, f)
+    }
+
+    fn join(self) {
+        ThreadPool::join(self)
+    }
+}
@@ -0,0 +1,112 @@
+//! This module contains the threads running the workers of ThreadPool, either owned by their pool or
+//! borrowed from the process-wide registry behind ThreadPool::shared
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+
+/// The number of worker threads spawned by every pool of the process, read by `ThreadPool::threads_spawned`
+static SPAWNED: AtomicUsize = AtomicUsize::new(0);
+
+/// The registry threads waiting for a worker loop to run, each reached through its own channel
+static IDLE: Mutex<Vec<Sender<Assignment>>> = Mutex::new(Vec::new());
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A worker loop handed to a registry thread, along with where to report its outcome
+struct Assignment {
+    job: Job,
+    done: futures::channel::oneshot::Sender<std::thread::Result<()>>,
+}
+
+/// The thread a worker runs on, joined by its pool
+pub(crate) enum WorkerThread {
+    /// A thread spawned for the worker, which exits along with it
+    Owned(std::thread::JoinHandle<()>),
+    /// A registry thread, which goes back to the registry once the worker has exited
+    Borrowed(futures::channel::oneshot::Receiver<std::thread::Result<()>>),
+    /// No thread, because the operating system failed to spawn one, so the worker never ran
+    Failed,
+}
+
+impl WorkerThread {
+    /// Runs `f` on a new thread, or on an idle registry thread if `shared` is set.
+    ///
+    /// The stack size only applies to new threads. Fails if the operating system fails to spawn the thread,
+    /// in which case `f` is dropped without running.
+    pub(crate) fn spawn(
+        shared: bool,
+        stack_size: Option<usize>,
+        f: impl FnOnce() + Send + 'static,
+    ) -> std::io::Result<Self> {
+        if shared {
+            return Self::borrow(Box::new(f));
+        }
+        let mut builder = std::thread::Builder::new();
+        if let Some(stack_size) = stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        let thread = builder.spawn(f)?;
+        SPAWNED.fetch_add(1, Ordering::Relaxed);
+        Ok(Self::Owned(thread))
+    }
+
+    /// Waits for the worker to exit, returning the payload of its panic if it panicked.
+    pub(crate) fn join(self) -> std::thread::Result<()> {
+        match self {
+            Self::Owned(thread) => thread.join(),
+            // Registry threads catch panics, so the outcome is always reported
+            Self::Borrowed(done) => futures::executor::block_on(done).unwrap_or(Ok(())),
+            Self::Failed => Ok(()),
+        }
+    }
+
+    /// Returns whether the worker has exited. Borrowed threads are only known to be done once joined.
+    pub(crate) fn is_finished(&self) -> bool {
+        match self {
+            Self::Owned(thread) => thread.is_finished(),
+            Self::Borrowed(_) => false,
+            Self::Failed => true,
+        }
+    }
+
+    fn borrow(job: Job) -> std::io::Result<Self> {
+        let (done, finished) = futures::channel::oneshot::channel();
+        let assignment = Assignment { job, done };
+        let idle = IDLE.lock().unwrap().pop();
+        match idle {
+            Some(thread) => {
+                // Registry threads never exit, but a new thread keeps the worker from being lost if one did
+                if let Err(std::sync::mpsc::SendError(assignment)) = thread.send(assignment) {
+                    spawn_registry_thread(assignment)?;
+                }
+            }
+            None => spawn_registry_thread(assignment)?,
+        }
+        Ok(Self::Borrowed(finished))
+    }
+}
+
+/// Returns the number of worker threads spawned so far by every pool of the process
+pub(crate) fn spawned() -> usize {
+    SPAWNED.load(Ordering::Relaxed)
+}
+
+/// Spawns a registry thread running `first`, then every worker loop assigned to it afterwards
+fn spawn_registry_thread(first: Assignment) -> std::io::Result<()> {
+    std::thread::Builder::new().spawn(move || {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut next = Some(first);
+        while let Some(Assignment { job, done }) = next.take().or_else(|| receiver.recv().ok()) {
+            let result = panic::catch_unwind(AssertUnwindSafe(job));
+            // Registered before reporting, so that the thread is idle by the time its pool is joined
+            IDLE.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(sender.clone());
+            let _ = done.send(result);
+        }
+    })?;
+    SPAWNED.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
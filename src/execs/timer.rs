@@ -0,0 +1,111 @@
+//! This module contains the timer thread shared by every pool of the process, which runs delayed actions
+//! such as resubmitting the tasks of ThreadPool::execute_retry after their backoff, and raising the flag of
+//! the tasks of ThreadPool::execute_timed_coop
+
+use std::collections::BinaryHeap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+/// An action run on the timer thread once its deadline has passed
+pub(crate) type Action = Box<dyn FnOnce() + Send + 'static>;
+
+/// The actions waiting for their deadline, and whether the timer thread has been spawned to run them
+static STATE: Mutex<TimerState> = Mutex::new(TimerState {
+    queue: BinaryHeap::new(),
+    seq: 0,
+    running: false,
+});
+
+/// Wakes the timer thread when an action is scheduled ahead of the ones it waits for
+static CHANGED: Condvar = Condvar::new();
+
+struct TimerState {
+    queue: BinaryHeap<Delayed>,
+    seq: u64,
+    running: bool,
+}
+
+/// Runs `action` on the timer thread once `deadline` has passed.
+///
+/// The timer thread is spawned on the first call and then serves the whole process, so actions should
+/// only do something quick, such as submitting a task or setting a flag. If the thread cannot be spawned,
+/// the action is handed back, for the caller to fall back on something else.
+pub(crate) fn schedule(deadline: Instant, action: Action) -> Result<(), Action> {
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    if !state.running {
+        // The new thread waits for the lock held here before looking at the queue
+        if std::thread::Builder::new().spawn(run).is_err() {
+            return Err(action);
+        }
+        state.running = true;
+    }
+    state.seq += 1;
+    let seq = state.seq;
+    state.queue.push(Delayed {
+        deadline,
+        seq,
+        action,
+    });
+    CHANGED.notify_one();
+    Ok(())
+}
+
+/// Runs the actions as their deadlines pass, for the rest of the process
+fn run() {
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        let now = Instant::now();
+        state = match state.queue.peek() {
+            Some(delayed) if delayed.deadline <= now => {
+                let delayed = state.queue.pop().expect("The queue has a first action");
+                drop(state);
+                // A panicking action must not stop the timer for every other pool
+                let _ = panic::catch_unwind(AssertUnwindSafe(delayed.action));
+                STATE.lock().unwrap_or_else(|e| e.into_inner())
+            }
+            Some(delayed) => {
+                let timeout = delayed.deadline - now;
+                CHANGED
+                    .wait_timeout(state, timeout)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0
+            }
+            None => CHANGED.wait(state).unwrap_or_else(|e| e.into_inner()),
+        };
+    }
+}
+
+/// An action queued by deadline, with a sequence number keeping equal deadlines in scheduling order
+struct Delayed {
+    deadline: Instant,
+    seq: u64,
+    action: Action,
+}
+
+impl Delayed {
+    fn key(&self) -> std::cmp::Reverse<(Instant, u64)> {
+        std::cmp::Reverse((self.deadline, self.seq))
+    }
+}
+
+impl PartialEq for Delayed {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Delayed {}
+
+impl PartialOrd for Delayed {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Delayed {
+    // `BinaryHeap` pops the greatest element, so the earliest deadline must compare as the greatest
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}